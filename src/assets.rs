@@ -0,0 +1,87 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use eyre::WrapErr;
+use walkdir::WalkDir;
+
+use crate::build::url_to_filepath;
+use crate::config::AssetConfig;
+
+/// A static file to copy into the public directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Asset {
+    pub source: PathBuf,
+    pub dest: PathBuf,
+}
+
+impl Asset {
+    /// Find every file to copy into the public directory, preserving their paths relative to the
+    /// asset directory they're in.
+    ///
+    /// This follows symlinks, so the files they point to are copied instead.
+    pub fn find_all(assets: &[AssetConfig], public_dir: &Path) -> eyre::Result<Vec<Self>> {
+        let mut files = Vec::new();
+
+        for asset in assets {
+            let dest_dir = url_to_filepath(public_dir, &asset.path);
+
+            for entry_result in WalkDir::new(&asset.dir)
+                .follow_links(true)
+                .sort_by_file_name()
+            {
+                let entry = entry_result.wrap_err(format!(
+                    "failed reading asset directory: {}",
+                    asset.dir.to_string_lossy()
+                ))?;
+
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+
+                let relative_path = entry.path().strip_prefix(&asset.dir)?;
+
+                if let Some(glob) = &asset.glob {
+                    if !glob.is_match(relative_path) {
+                        continue;
+                    }
+                }
+
+                files.push(Self {
+                    source: entry.path().to_owned(),
+                    dest: dest_dir.join(relative_path),
+                });
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Whether the file was already copied by a previous build and hasn't changed since.
+    ///
+    /// This is a heuristic based on the size and modification time of the files, like `make`.
+    pub fn is_unchanged(&self) -> bool {
+        let (source, dest) = match (fs::metadata(&self.source), fs::metadata(&self.dest)) {
+            (Ok(source), Ok(dest)) => (source, dest),
+            _ => return false,
+        };
+
+        match (source.modified(), dest.modified()) {
+            (Ok(source_modified), Ok(dest_modified)) => {
+                source.len() == dest.len() && dest_modified >= source_modified
+            }
+            _ => false,
+        }
+    }
+
+    /// Copy the file into the public directory. This copies the file's permissions as well.
+    pub fn copy(&self) -> eyre::Result<u64> {
+        if let Some(parent_dir) = self.dest.parent() {
+            fs::create_dir_all(parent_dir).wrap_err("failed creating parent directory")?;
+        }
+
+        fs::copy(&self.source, &self.dest).wrap_err(format!(
+            "failed copying asset file: {}",
+            self.source.to_string_lossy()
+        ))
+    }
+}
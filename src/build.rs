@@ -1,44 +1,98 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
-use eyre::{bail, WrapErr};
+use chrono::NaiveDate;
+use eyre::{bail, eyre, WrapErr};
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+use url::Url;
 
-use crate::config::Config;
-use crate::feed::Feed;
-use crate::template::{EntryTemplateData, FeedTemplateData};
+use crate::assets::Asset;
+use crate::cache::{CachedPost, Fingerprint, Manifest};
+use crate::config::{Config, FeedLinkPosition};
+use crate::entry::Entry;
+use crate::error::Error;
+use crate::feed::{Feed, FeedOptions};
+use crate::gemtext;
+use crate::hook::{run_post_build, run_sign_command};
+use crate::links::find_broken_links;
+use crate::report::{BuildReport, ReportFile};
+use crate::template::{
+    find_include_files, EntryLinkTemplateData, EntryTemplateData, FeedTemplateData,
+    PaginationTemplateData, RedirectTemplateData,
+};
 
-const FEED_TEMPLATE: &str = include_str!("atom.xml.tera");
+const RSS_TEMPLATE: &str = include_str!("rss.xml.tera");
+const GEMSUB_TEMPLATE: &str = include_str!("gemsub.gmi.tera");
+const REDIRECT_TEMPLATE: &str = include_str!("redirect.gmi.tera");
 
-fn url_to_filepath(base_path: &Path, url_path: &str) -> PathBuf {
+// Where the robots.txt and security.txt go, relative to the public directory.
+const ROBOTS_FILE: &str = "robots.txt";
+const SECURITY_FILE: &str = ".well-known/security.txt";
+
+// The suffix of the file with the checksum of a feed or index page.
+const CHECKSUM_FILE_SUFFIX: &str = ".sha256";
+
+pub fn url_to_filepath(base_path: &Path, url_path: &str) -> PathBuf {
     base_path.join(PathBuf::from_iter(
         url_path.split('/').filter(|segment| !segment.is_empty()),
     ))
 }
 
-// Recursively copy a directory.
-fn copy_dir(src: &Path, dest: &Path) -> eyre::Result<()> {
-    fs::create_dir_all(dest).wrap_err("failed creating dest directory")?;
+// Recursively copy a directory. In a dry run, we only print the paths of the files we would copy.
+fn copy_dir(src: &Path, dest: &Path, outputs: &mut OutputFiles) -> eyre::Result<()> {
+    let dry_run = outputs.dry_run;
 
-    let src_entries = fs::read_dir(src).wrap_err("failed reading directory contents")?;
+    if !dry_run {
+        fs::create_dir_all(dest).wrap_err("failed creating dest directory")?;
+    }
 
-    for src_entry_result in src_entries {
-        let src_entry = src_entry_result.wrap_err("failed reading directory entry")?;
+    // We copy files in a consistent order, so the dry run output and the build report are the
+    // same on every machine.
+    let mut src_entries = fs::read_dir(src)
+        .wrap_err("failed reading directory contents")?
+        .collect::<Result<Vec<_>, _>>()
+        .wrap_err("failed reading directory entry")?;
 
+    src_entries.sort_by_key(fs::DirEntry::path);
+
+    for src_entry in src_entries {
         let file_type = src_entry.file_type().wrap_err("failed reading file type")?;
 
         let src_path = src_entry.path();
         let dest_path = dest.join(src_path.strip_prefix(src)?);
 
-        if file_type.is_file() {
+        // This follows symlinks, so we record the size of the file they point to.
+        if !file_type.is_dir() {
+            let size = fs::metadata(&src_path).map_or(0, |metadata| metadata.len());
+            outputs.record(&dest_path, size);
+        }
+
+        if dry_run && !file_type.is_dir() {
+            println!("{}", dest_path.to_string_lossy());
+        } else if file_type.is_file() {
+            if outputs.skip_unchanged && files_match(&src_path, &dest_path) {
+                debug!(
+                    "Skipped {} because it hasn't changed",
+                    dest_path.to_string_lossy()
+                );
+                continue;
+            }
+
             // Truncate the dest file if it already exists.
             fs::copy(&src_path, &dest_path).wrap_err("failed copying regular file")?;
+            debug!(
+                "Copied {} → {}",
+                src_path.to_string_lossy(),
+                dest_path.to_string_lossy()
+            );
         } else if file_type.is_dir() {
-            // Don't fail if the dest dir already exists.
-            fs::create_dir_all(&dest_path).wrap_err("failed creating new directory in dest dir")?;
-
-            // Recursively copy contents.
-            copy_dir(&src_path, &dest_path)?;
+            // Recursively copy contents. This doesn't fail if the dest dir already exists.
+            copy_dir(&src_path, &dest_path, outputs)?;
         } else if file_type.is_symlink() {
             let link_dest = fs::read_link(&src_path).wrap_err("failed reading link dest")?;
 
@@ -66,57 +120,1049 @@ fn copy_dir(src: &Path, dest: &Path) -> eyre::Result<()> {
     Ok(())
 }
 
-pub fn build_capsule(config: &Config) -> eyre::Result<()> {
-    let warn_handler = |msg: &str| eprintln!("Warning: {}", msg);
+// Whether two files have the same contents. If either can't be read, we assume they don't.
+fn files_match(a: &Path, b: &Path) -> bool {
+    match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(a_metadata), Ok(b_metadata)) if a_metadata.len() == b_metadata.len() => {}
+        _ => return false,
+    }
+
+    matches!((fs::read(a), fs::read(b)), (Ok(a_contents), Ok(b_contents)) if a_contents == b_contents)
+}
+
+// Writes the files generated by a build and keeps track of them for the build report.
+#[derive(Debug)]
+struct OutputFiles<'a> {
+    public_dir: &'a Path,
+    dry_run: bool,
+    // Whether to leave files alone when they already have the contents we would write.
+    skip_unchanged: bool,
+    // Whether to write a checksum file next to each feed and index page.
+    checksums: bool,
+    files: BTreeMap<PathBuf, u64>,
+    // The feeds and index pages, which subscribers might want to verify.
+    verifiable: Vec<PathBuf>,
+}
+
+impl<'a> OutputFiles<'a> {
+    fn new(public_dir: &'a Path, dry_run: bool, skip_unchanged: bool, checksums: bool) -> Self {
+        Self {
+            public_dir,
+            dry_run,
+            skip_unchanged,
+            checksums,
+            files: BTreeMap::new(),
+            verifiable: Vec::new(),
+        }
+    }
+
+    // The paths of every file in the public directory this build generated or kept.
+    fn paths(&self) -> HashSet<PathBuf> {
+        self.files
+            .keys()
+            .map(|path| self.public_dir.join(path))
+            .collect()
+    }
+
+    // Record a file in the public directory. If a file is generated more than once, the last one
+    // wins.
+    fn record(&mut self, path: &Path, size: u64) {
+        let relative_path = path.strip_prefix(self.public_dir).unwrap_or(path);
+        self.files.insert(relative_path.to_owned(), size);
+    }
+
+    // Write a generated file, creating its parent directories. In a dry run, we only print the
+    // path of the file instead.
+    fn write(&mut self, path: &Path, contents: &str) -> eyre::Result<()> {
+        self.record(path, contents.len() as u64);
+
+        if self.dry_run {
+            println!("{}", path.to_string_lossy());
+            return Ok(());
+        }
+
+        let parent_dir = path.parent().ok_or(eyre!(
+            "Could not get parent directory of generated file. This is a bug."
+        ))?;
+
+        fs::create_dir_all(parent_dir).wrap_err("failed creating parent directory")?;
+
+        if self.skip_unchanged
+            && fs::read(path).is_ok_and(|existing| existing == contents.as_bytes())
+        {
+            debug!(
+                "Skipped {} because it hasn't changed",
+                path.to_string_lossy()
+            );
+            return Ok(());
+        }
+
+        fs::write(path, contents)
+            .wrap_err(format!("failed writing file: {}", path.to_string_lossy()))?;
+
+        debug!("Generated {}", path.to_string_lossy());
+
+        Ok(())
+    }
+
+    // Write a feed or index page, along with its checksum file if enabled. The checksum file is in
+    // the same format as the output of `sha256sum`, so it can be checked with `sha256sum --check`.
+    fn write_verifiable(&mut self, path: &Path, contents: &str) -> eyre::Result<()> {
+        self.write(path, contents)?;
+
+        if self.checksums {
+            let checksum = Sha256::digest(contents)
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>();
+
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+
+            let mut checksum_path = path.as_os_str().to_owned();
+            checksum_path.push(CHECKSUM_FILE_SUFFIX);
+
+            self.write(
+                Path::new(&checksum_path),
+                &format!("{checksum}  {file_name}\n"),
+            )?;
+        }
+
+        self.verifiable.push(path.to_owned());
+
+        Ok(())
+    }
+
+    fn into_report_files(self) -> Vec<ReportFile> {
+        self.files
+            .into_iter()
+            .map(|(path, size)| ReportFile { path, size })
+            .collect()
+    }
+}
+
+// Recursively delete everything in a directory except the given files, and then delete any
+// directories which are left empty. This returns whether the directory is now empty.
+fn clean_dir(dir: &Path, keep: &HashSet<PathBuf>) -> eyre::Result<bool> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(true),
+        Err(err) => return Err(err).wrap_err("failed reading directory contents"),
+    };
+
+    let mut is_empty = true;
+
+    for entry_result in entries {
+        let entry = entry_result.wrap_err("failed reading directory entry")?;
+        let file_type = entry.file_type().wrap_err("failed reading file type")?;
+        let path = entry.path();
+
+        if file_type.is_dir() {
+            if clean_dir(&path, keep)? {
+                fs::remove_dir(&path).wrap_err("failed removing empty directory")?;
+            } else {
+                is_empty = false;
+            }
+        } else if keep.contains(&path) {
+            is_empty = false;
+        } else {
+            // This also removes symlinks without following them.
+            fs::remove_file(&path).wrap_err("failed removing file")?;
+        }
+    }
+
+    Ok(is_empty)
+}
+
+// The fields of the other entries in the feed which come from their bodies or their files rather
+// than their metadata. These change whenever a post is edited, so leaving them out of the
+// fingerprint means editing one post doesn't re-render every other post.
+const ENTRY_BODY_FIELDS: &[&str] = &[
+    "body",
+    "teaser",
+    "summary",
+    "checksum",
+    "file_modified",
+    "word_count",
+    "reading_time",
+    "link_count",
+    "internal_link_count",
+    "external_link_count",
+    "headings",
+    "preformatted_blocks",
+];
+
+// Compute a fingerprint of everything that goes into rendering a post page, so we can tell whether
+// it needs to be re-rendered.
+//
+// Post templates can see the whole feed, so this covers the feed's own fields and the metadata of
+// every entry in it. That means adding, removing, or retitling a post re-renders every post page,
+// but editing the body of a post only re-renders that post.
+fn post_fingerprint(
+    shared: &str,
+    feed: &FeedTemplateData,
+    entry: &EntryTemplateData,
+    prev: Option<&EntryLinkTemplateData>,
+    next: Option<&EntryLinkTemplateData>,
+    template: Option<&Path>,
+) -> eyre::Result<String> {
+    let mut fingerprint = Fingerprint::new();
+    fingerprint.update(shared);
+    let mut feed_value = serde_json::to_value(feed).wrap_err("failed serializing feed")?;
+
+    for key in ["entries", "unlisted_entries"] {
+        let feed_entries = feed_value
+            .get_mut(key)
+            .and_then(|value| value.as_array_mut());
+
+        for feed_entry in feed_entries.into_iter().flatten() {
+            if let Some(feed_entry) = feed_entry.as_object_mut() {
+                for field in ENTRY_BODY_FIELDS {
+                    feed_entry.remove(*field);
+                }
+            }
+        }
+    }
+
+    fingerprint.update(feed_value.to_string());
+
+    // The build time changes every build, so post pages only show when they were last rendered,
+    // but the post count is the same unless a post was added or removed.
+    fingerprint.update(feed.site.post_count.to_string());
+
+    // The `post_url` function isn't part of the serialized feed, but a post which uses it needs to
+    // be re-rendered when the post it links to moves.
+    fingerprint
+        .update(serde_json::to_vec(&feed.post_urls).wrap_err("failed serializing post URLs")?);
+    fingerprint.update(serde_json::to_vec(entry).wrap_err("failed serializing entry")?);
+    fingerprint.update(serde_json::to_vec(&prev).wrap_err("failed serializing entry link")?);
+    fingerprint.update(serde_json::to_vec(&next).wrap_err("failed serializing entry link")?);
+
+    // The post's own template, if it has one, isn't part of the shared fingerprint.
+    if let Some(template) = template {
+        fingerprint.update(fs::read(template).unwrap_or_default());
+    }
+
+    Ok(fingerprint.finish())
+}
+
+// Compute a fingerprint of the inputs shared by every post page. When this changes, every post
+// needs to be re-rendered.
+fn shared_fingerprint(config: &Config) -> String {
+    let mut fingerprint = Fingerprint::new();
+
+    fingerprint.update(env!("CARGO_PKG_VERSION"));
+    fingerprint.update(format!("{config:?}"));
+
+    // If we can't read the template, rendering the post will fail with a more useful error.
+    fingerprint.update(fs::read(&config.post_template_file).unwrap_or_default());
+
+    if let Some(includes_dir) = &config.includes_dir {
+        for (path, name) in find_include_files(includes_dir).unwrap_or_default() {
+            fingerprint.update(name);
+            fingerprint.update(fs::read(path).unwrap_or_default());
+        }
+    }
+
+    fingerprint.finish()
+}
+
+/// Tidy up a generated gemtext page, if the config asks for it.
+///
+/// This normalizes the whitespace and then wraps long lines, so the wrapping sees the final text.
+pub fn tidy_gemtext(config: &Config, contents: String) -> String {
+    let contents = if config.normalize_whitespace {
+        gemtext::normalize_whitespace(&contents)
+    } else {
+        contents
+    };
+
+    match config.wrap_width {
+        Some(width) => gemtext::wrap_lines(&contents, width),
+        None => contents,
+    }
+}
+
+/// Add a link to the feed to a generated index or post page, if the config asks for it.
+///
+/// This comes before tidying up the page, so the link is wrapped and spaced like the rest of it.
+pub fn add_feed_link(config: &Config, contents: String, feed_url: &str) -> String {
+    let feed_link = match &config.feed_link {
+        Some(feed_link) => feed_link,
+        None => return contents,
+    };
+
+    let link_line = format!("=> {feed_url} {}", feed_link.text);
+
+    match feed_link.position {
+        FeedLinkPosition::Top => format!("{link_line}\n\n{contents}"),
+        FeedLinkPosition::Bottom if contents.is_empty() || contents.ends_with('\n') => {
+            format!("{contents}\n{link_line}\n")
+        }
+        FeedLinkPosition::Bottom => format!("{contents}\n\n{link_line}\n"),
+    }
+}
+
+/// A post page to render, along with the posts it links to as the previous and next posts.
+pub struct PostPage<'a> {
+    pub entry: &'a Entry,
+    pub data: &'a EntryTemplateData,
+    pub prev: Option<&'a EntryLinkTemplateData>,
+    pub next: Option<&'a EntryLinkTemplateData>,
+}
+
+/// Get every post page to render.
+///
+/// Unlisted posts have no previous or next posts, and they're never the previous or next post of
+/// another post.
+pub fn post_pages<'a>(
+    feed: &'a Feed,
+    feed_data: &'a FeedTemplateData,
+    entry_links: &'a [EntryLinkTemplateData],
+) -> Vec<PostPage<'a>> {
+    // The feed entries and the entries in the template data are in the same order, which is
+    // reverse-chronological by default. The previous (older) post comes after the current one and
+    // the next (newer) post comes before it.
+    let listed =
+        feed.entries
+            .iter()
+            .zip(&feed_data.entries)
+            .enumerate()
+            .map(|(index, (entry, data))| PostPage {
+                entry,
+                data,
+                prev: entry_links.get(index + 1),
+                next: index
+                    .checked_sub(1)
+                    .and_then(|next_index| entry_links.get(next_index)),
+            });
+
+    let unlisted = feed
+        .unlisted_entries
+        .iter()
+        .zip(&feed_data.unlisted_entries)
+        .map(|(entry, data)| PostPage {
+            entry,
+            data,
+            prev: None,
+            next: None,
+        });
+
+    listed.chain(unlisted).collect()
+}
+
+/// Options for building the capsule which come from the command line rather than the config file.
+#[derive(Debug, Default)]
+pub struct BuildOptions {
+    /// Whether to include draft posts.
+    pub drafts: bool,
+
+    /// Whether to include posts which have expired.
+    pub ignore_expiry: bool,
+
+    /// The earliest date a post can be published on to be included.
+    pub since: Option<NaiveDate>,
+
+    /// The latest date a post can be published on to be included.
+    pub until: Option<NaiveDate>,
+
+    /// Whether to include posts without a publish date when `since` or `until` is set.
+    pub include_unpublished: bool,
+
+    /// Whether to ignore the build cache and re-render every post.
+    pub force: bool,
+
+    /// Whether to warn rather than fail when a post was published after it was updated.
+    pub lenient_dates: bool,
+
+    /// Whether to fail if there are any warnings, like a post missing its gemtext or metadata file.
+    pub strict: bool,
+
+    /// Whether to print the files we would write instead of writing them.
+    pub dry_run: bool,
+
+    /// Whether to check the generated pages for broken links.
+    pub check_links: bool,
+
+    /// The profile in the config file to use, if any.
+    pub profile: Option<String>,
+
+    /// The directory to generate the capsule in, which overrides the `public_dir` in the config.
+    pub output: Option<PathBuf>,
+
+    /// Where to write a JSON report of the build.
+    pub report: Option<PathBuf>,
+
+    /// The URL to build the capsule for, which overrides the `url` in the config.
+    pub capsule_url: Option<Url>,
+}
+
+impl BuildOptions {
+    /// The directory to generate the capsule in.
+    pub fn public_dir<'a>(&'a self, config: &'a Config) -> &'a Path {
+        self.output.as_deref().unwrap_or(&config.public_dir)
+    }
+
+    /// Override the values in the config which these options replace.
+    ///
+    /// This needs to happen before building, because the capsule URL is used everywhere.
+    pub fn apply_to_config(&self, config: &mut Config) {
+        if let Some(capsule_url) = &self.capsule_url {
+            config.url = capsule_url.clone();
+        }
+    }
+}
+
+/// Print the files each post would be read from, without building anything.
+pub fn print_post_sources(config: &Config, options: &BuildOptions) -> eyre::Result<()> {
+    let path_pairs = Entry::find_posts(
+        &config.posts_dir,
+        &config.post_files,
+        options.strict,
+        |msg| warn!("{}", msg),
+    )
+    .wrap_err("failed finding posts")?;
+
+    for pair in path_pairs {
+        println!("{}", pair.post.to_string_lossy());
+
+        if let Some(metadata) = pair.metadata {
+            println!("{}", metadata.to_string_lossy());
+        }
+    }
+
+    Ok(())
+}
+
+pub fn build_capsule(config: &Config, options: &BuildOptions) -> eyre::Result<()> {
+    // We keep track of warnings so we can include them in the build report.
+    let warnings = RefCell::new(Vec::new());
+    let started = Instant::now();
+
+    let warn_handler = |msg: &str| {
+        warn!("{}", msg);
+        warnings.borrow_mut().push(msg.to_owned());
+    };
+    let public_dir = options.public_dir(config);
+
+    let feed_options = FeedOptions {
+        include_drafts: options.drafts,
+        ignore_expiry: options.ignore_expiry,
+        since: options.since,
+        until: options.until,
+        include_unpublished: options.include_unpublished,
+        lenient_dates: options.lenient_dates,
+        // Even with `--strict`, missing files are only warnings here, so they're reported along
+        // with every other warning rather than failing the build on their own.
+        strict_post_files: false,
+        warn_missing_alt_text: options.strict,
+    };
+
+    let feed = Feed::from_config(config, feed_options, warn_handler)
+        .wrap_err("failed parsing config file")?;
+
+    // The syndication feeds are structured data, so they always have the real email addresses, even
+    // if they're obfuscated in the pages.
+    let syndication_data = FeedTemplateData::from(feed.clone());
+    let feed_data = if config.obfuscate_emails {
+        syndication_data.with_obfuscated_emails()
+    } else {
+        syndication_data.clone()
+    };
+
+    let entry_links = feed_data
+        .entries
+        .iter()
+        .map(EntryLinkTemplateData::from)
+        .collect::<Vec<_>>();
+
+    let post_pages = post_pages(&feed, &feed_data, &entry_links);
+
+    // Work out which posts have changed since the last build.
+
+    let previous_manifest = if options.force {
+        None
+    } else {
+        Manifest::read(&config.cache_file, warn_handler)
+            .wrap_err("failed reading the build cache")?
+    };
+
+    // In strict mode, every warning so far fails the build. We report them all at once, before
+    // anything is written, so they can all be fixed in one go.
+
+    if options.strict && !warnings.borrow().is_empty() {
+        let reason = warnings
+            .borrow()
+            .iter()
+            .map(|warning| format!("- {warning}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        bail!(Error::StrictWarnings { reason });
+    }
+
+    let shared_fingerprint = shared_fingerprint(config);
+    let mut manifest = Manifest::new();
+    let mut fresh_posts = HashSet::new();
+
+    for &PostPage {
+        entry,
+        data: entry_data,
+        prev,
+        next,
+    } in &post_pages
+    {
+        let cached_post = CachedPost {
+            output: public_dir.join(&entry.path),
+            fingerprint: post_fingerprint(
+                &shared_fingerprint,
+                &feed_data,
+                entry_data,
+                prev,
+                next,
+                entry.metadata.template.as_deref(),
+            )
+            .wrap_err("failed computing post fingerprint")?,
+        };
+
+        // A dry run renders every post, so it lists every file and checks every template.
+        if let Some(previous_manifest) = previous_manifest.as_ref().filter(|_| !options.dry_run) {
+            if previous_manifest.is_fresh(&entry.source_path, &cached_post) {
+                fresh_posts.insert(cached_post.output.clone());
+            }
+        }
+
+        manifest
+            .posts
+            .insert(entry.source_path.clone(), cached_post);
+    }
 
-    let feed = Feed::from_config(config, warn_handler).wrap_err("failed parsing config file")?;
-    let feed_data = FeedTemplateData::from(feed.clone());
+    // Work out which asset files have changed since the last build. We only skip copying them if
+    // we're doing an incremental build.
 
-    // Delete the public dir. We do this because static files might have been removed since the
-    // last build, and posts might have been removed or converted to drafts. It's easier to just
-    // start with a new empty directory.
+    let assets = Asset::find_all(&config.assets, public_dir).wrap_err("failed finding assets")?;
+    let mut fresh_assets = HashSet::new();
 
-    match fs::remove_dir_all(&config.public_dir) {
-        // The public dir not existing is not an error.
-        Err(err) if err.kind() != io::ErrorKind::NotFound => {
-            Err(err).wrap_err("failed removing the public directory")?
+    if previous_manifest.is_some() {
+        for asset in &assets {
+            if asset.is_unchanged() {
+                fresh_assets.insert(asset.dest.clone());
+            }
         }
-        _ => {}
     }
 
-    fs::create_dir_all(&config.public_dir).wrap_err("failed creating the public directory")?;
+    // Clear out the public dir, except for posts and assets which haven't changed. We do this
+    // because static files might have been removed since the last build, and posts might have been
+    // removed or converted to drafts. It's easier to just start with an empty directory.
+
+    if options.dry_run {
+        // We don't touch the public directory in a dry run.
+    } else if config.skip_unchanged_writes {
+        // We need the files from the last build to compare against, so we clean up whatever this
+        // build doesn't generate at the end instead.
+    } else if previous_manifest.is_some() {
+        let keep = fresh_posts.union(&fresh_assets).cloned().collect();
+        clean_dir(public_dir, &keep).wrap_err("failed cleaning the public directory")?;
+    } else {
+        match fs::remove_dir_all(public_dir) {
+            // The public dir not existing is not an error.
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                Err(err).wrap_err("failed removing the public directory")?
+            }
+            _ => {}
+        }
+    }
+
+    if !options.dry_run {
+        fs::create_dir_all(public_dir).wrap_err(format!(
+            "failed creating the output directory: {}",
+            public_dir.to_string_lossy()
+        ))?;
+    }
+
+    let mut outputs = OutputFiles::new(
+        public_dir,
+        options.dry_run,
+        config.skip_unchanged_writes,
+        config.feed_checksums,
+    );
 
-    // Generate the index page.
+    // Generate the index pages.
 
-    let index_page_path = url_to_filepath(&config.public_dir, &config.index_path);
-    feed_data
-        .render_index(&config.index_template_file, &index_page_path)
-        .wrap_err("failed rendering index page")?;
+    for (index, page) in feed.index_pages.iter().enumerate() {
+        let index_page_path = public_dir.join(&page.path);
+        let pagination = PaginationTemplateData::new(&feed.index_pages, index);
+
+        let contents = feed_data
+            .render_index(
+                &feed_data.entries[page.entries.clone()],
+                &pagination,
+                &config.index_template_file,
+            )
+            .wrap_err(format!(
+                "failed rendering index page: {}",
+                index_page_path.to_string_lossy()
+            ))?;
+
+        outputs.write_verifiable(
+            &index_page_path,
+            &tidy_gemtext(config, add_feed_link(config, contents, &feed_data.feed_url)),
+        )?;
+    }
 
     // Generate the Atom feed.
 
-    let feed_path = url_to_filepath(&config.public_dir, &config.feed_path);
-    feed_data
-        .render_feed(FEED_TEMPLATE, &feed_path)
+    let feed_path = url_to_filepath(public_dir, &config.feed_path);
+    let contents = syndication_data
+        .render_feed(config.feed_template_file.as_deref(), config.feed_limit)
         .wrap_err("failed rendering Atom feed")?;
 
-    // Generate the individual posts.
+    outputs.write_verifiable(&feed_path, &contents)?;
+
+    // Generate the RSS feed, if enabled.
+
+    if let Some(rss_path) = &config.rss_path {
+        let rss_path = url_to_filepath(public_dir, rss_path);
+        let contents = syndication_data
+            .render_rss(RSS_TEMPLATE, config.feed_limit)
+            .wrap_err("failed rendering RSS feed")?;
+
+        outputs.write_verifiable(&rss_path, &contents)?;
+    }
+
+    // Generate the gemsub feed, if enabled.
+
+    if let Some(gemsub_path) = &config.gemsub_path {
+        let gemsub_path = url_to_filepath(public_dir, gemsub_path);
+        let contents = feed_data
+            .render_gemsub(GEMSUB_TEMPLATE)
+            .wrap_err("failed rendering gemsub feed")?;
+
+        outputs.write_verifiable(&gemsub_path, &tidy_gemtext(config, contents))?;
+    }
+
+    // Generate the JSON Feed, if enabled.
+
+    if let Some(json_feed_path) = &config.json_feed_path {
+        let json_feed_path = url_to_filepath(public_dir, json_feed_path);
+        let contents = syndication_data
+            .render_json_feed(config.feed_limit)
+            .wrap_err("failed rendering JSON Feed")?;
+
+        outputs.write_verifiable(&json_feed_path, &contents)?;
+    }
+
+    // Generate the index page and Atom feed of each subfeed.
+
+    for subfeed in &feed.subfeeds {
+        let subfeed_data = feed_data.subfeed(subfeed);
+        let index_page_path = public_dir.join(&subfeed.index_page.path);
+        let pagination = PaginationTemplateData::new(std::slice::from_ref(&subfeed.index_page), 0);
+
+        let contents = subfeed_data
+            .render_index(
+                &subfeed_data.entries,
+                &pagination,
+                &config.index_template_file,
+            )
+            .wrap_err(format!(
+                "failed rendering the index page of the `{}` subfeed: {}",
+                subfeed.name,
+                index_page_path.to_string_lossy()
+            ))?;
+
+        outputs.write_verifiable(
+            &index_page_path,
+            &tidy_gemtext(
+                config,
+                add_feed_link(config, contents, &subfeed_data.feed_url),
+            ),
+        )?;
+
+        let feed_path = url_to_filepath(public_dir, &subfeed.feed_path);
+        let contents = syndication_data
+            .subfeed(subfeed)
+            .render_feed(config.feed_template_file.as_deref(), config.feed_limit)
+            .wrap_err(format!(
+                "failed rendering the Atom feed of the `{}` subfeed",
+                subfeed.name
+            ))?;
+
+        outputs.write_verifiable(&feed_path, &contents)?;
+    }
 
-    for entry in feed.entries {
-        let post_path = config.public_dir.join(&entry.path);
+    // Generate the individual posts, skipping those which haven't changed.
 
-        EntryTemplateData::from(entry)
-            .render(&feed_data, &config.post_template_file, &post_path)
+    for &PostPage {
+        entry,
+        data: entry_data,
+        prev,
+        next,
+    } in &post_pages
+    {
+        let post_path = public_dir.join(&entry.path);
+
+        if fresh_posts.contains(&post_path) {
+            let size = fs::metadata(&post_path).map_or(0, |metadata| metadata.len());
+            outputs.record(&post_path, size);
+            debug!(
+                "Skipped {} because it hasn't changed",
+                post_path.to_string_lossy()
+            );
+            continue;
+        }
+
+        // A template in the metadata file takes precedence over the one in the config.
+        let template = entry
+            .metadata
+            .template
+            .as_deref()
+            .unwrap_or(&config.post_template_file);
+
+        let contents = entry_data
+            .render(&feed_data, prev, next, template, &post_path)
             .wrap_err(format!(
                 "failed rendering post: {}",
                 post_path.to_string_lossy()
             ))?;
+
+        outputs.write(
+            &post_path,
+            &tidy_gemtext(config, add_feed_link(config, contents, &feed_data.feed_url)),
+        )?;
+    }
+
+    // Generate the archive page, if enabled.
+
+    if let Some(archive_template_file) = &config.archive_template_file {
+        let archive_page_path = url_to_filepath(public_dir, &config.archive_path);
+        let contents = feed_data
+            .render_archive(archive_template_file)
+            .wrap_err("failed rendering archive page")?;
+
+        outputs.write(&archive_page_path, &tidy_gemtext(config, contents))?;
+    }
+
+    // Generate the robots.txt and security.txt, if enabled. These aren't gemtext, so we leave them
+    // as they are.
+
+    if let Some(robots_template_file) = &config.robots_template_file {
+        let contents = feed_data
+            .render_robots(robots_template_file)
+            .wrap_err("failed rendering robots.txt")?;
+
+        outputs.write(&public_dir.join(ROBOTS_FILE), &contents)?;
+    }
+
+    if let Some(security_template_file) = &config.security_template_file {
+        let contents = feed_data
+            .render_security(security_template_file)
+            .wrap_err("failed rendering security.txt")?;
+
+        outputs.write(&public_dir.join(SECURITY_FILE), &contents)?;
+    }
+
+    // Generate the redirect pages for posts which have moved, using the bundled template unless the
+    // user provided their own.
+
+    if !feed.redirects.is_empty() {
+        let redirect_template = match &config.redirect_template_file {
+            Some(path) => fs::read_to_string(path).wrap_err(format!(
+                "failed reading redirect template file: {}",
+                path.to_string_lossy()
+            ))?,
+            None => REDIRECT_TEMPLATE.to_owned(),
+        };
+
+        for redirect in &feed.redirects {
+            let redirect_path = public_dir.join(&redirect.path);
+
+            let contents = feed_data
+                .render_redirect(
+                    &RedirectTemplateData::from(redirect.clone()),
+                    &redirect_template,
+                )
+                .wrap_err(format!(
+                    "failed rendering redirect page: {}",
+                    redirect_path.to_string_lossy()
+                ))?;
+
+            outputs.write(&redirect_path, &tidy_gemtext(config, contents))?;
+        }
+    }
+
+    // Generate the category pages, if enabled.
+
+    if let Some(category_template_file) = &config.category_template_file {
+        // The feed categories and the categories in the template data are in the same order.
+        for (category, category_data) in feed.categories.iter().zip(&feed_data.categories) {
+            let category_path = public_dir.join(&category.path);
+
+            let contents = feed_data
+                .render_category(category_data, category_template_file)
+                .wrap_err(format!(
+                    "failed rendering category page: {}",
+                    category_path.to_string_lossy()
+                ))?;
+
+            outputs.write(&category_path, &tidy_gemtext(config, contents))?;
+        }
+    }
+
+    if let Some(categories_template_file) = &config.categories_template_file {
+        let categories_page_path = url_to_filepath(public_dir, &config.categories_path);
+        let contents = feed_data
+            .render_categories(categories_template_file)
+            .wrap_err("failed rendering category index page")?;
+
+        outputs.write(&categories_page_path, &tidy_gemtext(config, contents))?;
+    }
+
+    // Copy over assets, skipping those which haven't changed. This clobbers any files generated in
+    // previous steps.
+
+    for asset in &assets {
+        let size = if options.dry_run || fresh_assets.contains(&asset.dest) {
+            fs::metadata(&asset.source).map_or(0, |metadata| metadata.len())
+        } else {
+            let size = asset.copy()?;
+            debug!(
+                "Copied {} → {}",
+                asset.source.to_string_lossy(),
+                asset.dest.to_string_lossy()
+            );
+            size
+        };
+
+        if options.dry_run {
+            println!("{}", asset.dest.to_string_lossy());
+        }
+
+        outputs.record(&asset.dest, size);
     }
 
     // Copy over static content. This clobbers any files generated in previous steps.
 
-    copy_dir(&config.static_dir, &config.public_dir)
+    copy_dir(&config.static_dir, public_dir, &mut outputs)
         .wrap_err("failed copying static content to the public directory")?;
 
+    // Remove anything left over from the last build, now that we know everything this build
+    // generated.
+
+    if config.skip_unchanged_writes && !options.dry_run {
+        clean_dir(public_dir, &outputs.paths()).wrap_err("failed cleaning the public directory")?;
+    }
+
+    // Sign the feeds and index pages, if enabled. This comes after cleaning up the public
+    // directory, since we don't know which files the signing command writes.
+
+    if let Some(command) = &config.sign_command {
+        if !options.dry_run {
+            for path in &outputs.verifiable {
+                run_sign_command(command, public_dir, path)?;
+            }
+        }
+    }
+
+    // Only record the build in the cache once it has succeeded.
+
+    if !options.dry_run {
+        manifest
+            .write(&config.cache_file)
+            .wrap_err("failed writing the build cache")?;
+    }
+
+    // Check for broken links once every page has been generated. We can't do this in a dry run,
+    // because nothing has been generated.
+
+    if options.check_links && !options.dry_run {
+        let broken_links = find_broken_links(public_dir, &config.url, &config.gemtext_extension)
+            .wrap_err("failed checking for broken links")?;
+
+        if !broken_links.is_empty() {
+            let reason = broken_links
+                .iter()
+                .map(|link| {
+                    format!(
+                        "- {}:{}: {}",
+                        link.source.to_string_lossy(),
+                        link.line,
+                        link.url
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            bail!(Error::BrokenLinks { reason });
+        }
+    }
+
+    let file_count = outputs.files.len();
+
+    // Only write the report once the build has succeeded.
+
+    if let Some(report_path) = &options.report {
+        let report = BuildReport {
+            dry_run: options.dry_run,
+            entries: feed.entries.len(),
+            skipped_drafts: feed.skipped_drafts,
+            skipped_expired: feed.skipped_expired,
+            skipped_out_of_range: feed.skipped_out_of_range,
+            updated: feed_data.updated.clone(),
+            files: outputs.into_report_files(),
+            warnings: warnings.take(),
+        };
+
+        report
+            .write(report_path)
+            .wrap_err("failed writing the build report")?;
+    }
+
+    // Run the post-build command last, so it sees every file this build wrote. In a dry run,
+    // nothing was written, so there's nothing for it to do.
+
+    if let Some(command) = &config.post_build {
+        if !options.dry_run {
+            run_post_build(command, public_dir)?;
+        }
+    }
+
+    if options.dry_run {
+        info!(
+            "Checked {} post(s) in {:.2?}. A build would write {file_count} file(s).",
+            post_pages.len(),
+            started.elapsed(),
+        );
+    } else {
+        info!(
+            "Built {} post(s) ({} rendered, {} unchanged) and {file_count} file(s) in {:.2?}.",
+            post_pages.len(),
+            post_pages.len() - fresh_posts.len(),
+            fresh_posts.len(),
+            started.elapsed(),
+        );
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A capsule in a temporary directory, with its posts as single files with front matter.
+    struct TestCapsule {
+        dir: PathBuf,
+    }
+
+    impl TestCapsule {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("gempost-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(dir.join("posts")).unwrap();
+
+            fs::write(
+                dir.join("gempost.yaml"),
+                format!(
+                    "title: \"Test\"\nurl: \"gemini://example.com\"\nposts_dir: \"{}\"\npost_template_file: \"{}\"\n",
+                    dir.join("posts").to_string_lossy(),
+                    dir.join("post.tera").to_string_lossy(),
+                ),
+            )
+            .unwrap();
+
+            let capsule = Self { dir };
+            capsule.write_template("{{ entry.body }}");
+            capsule.write_post("first", "First", "The first post.");
+            capsule.write_post("second", "Second", "The second post.");
+            capsule
+        }
+
+        fn write_template(&self, template: &str) {
+            fs::write(self.dir.join("post.tera"), template).unwrap();
+        }
+
+        fn write_post(&self, slug: &str, title: &str, body: &str) {
+            fs::write(
+                self.dir.join("posts").join(format!("{slug}.gmi")),
+                format!(
+                    "---\nid: \"urn:test:{slug}\"\ntitle: \"{title}\"\nupdated: \"2024-01-01\"\n---\n{body}\n"
+                ),
+            )
+            .unwrap();
+        }
+
+        // The fingerprint of each post page, by its slug.
+        fn fingerprints(&self) -> BTreeMap<String, String> {
+            let config = Config::read(&self.dir.join("gempost.yaml"), None).unwrap();
+            let feed = Feed::from_config(&config, FeedOptions::default(), |_| {}).unwrap();
+            let feed_data = FeedTemplateData::from(feed.clone());
+            let entry_links = feed_data
+                .entries
+                .iter()
+                .map(EntryLinkTemplateData::from)
+                .collect::<Vec<_>>();
+            let shared = shared_fingerprint(&config);
+
+            post_pages(&feed, &feed_data, &entry_links)
+                .into_iter()
+                .map(|page| {
+                    let slug = page.entry.source_path.file_stem().unwrap();
+                    let fingerprint = post_fingerprint(
+                        &shared, &feed_data, page.data, page.prev, page.next, None,
+                    )
+                    .unwrap();
+
+                    (slug.to_string_lossy().into_owned(), fingerprint)
+                })
+                .collect()
+        }
+    }
+
+    impl Drop for TestCapsule {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn editing_a_body_only_changes_that_post() {
+        let capsule = TestCapsule::new("fingerprint-body");
+        let before = capsule.fingerprints();
+
+        capsule.write_post("first", "First", "The first post.\n\nWith another line.");
+        let after = capsule.fingerprints();
+
+        assert_ne!(before["first"], after["first"]);
+        assert_eq!(before["second"], after["second"]);
+    }
+
+    #[test]
+    fn changing_the_template_changes_every_post() {
+        let capsule = TestCapsule::new("fingerprint-template");
+        let before = capsule.fingerprints();
+
+        capsule.write_template("# {{ entry.title }}\n\n{{ entry.body }}");
+        let after = capsule.fingerprints();
+
+        assert_ne!(before["first"], after["first"]);
+        assert_ne!(before["second"], after["second"]);
+    }
+
+    #[test]
+    fn changing_the_entries_changes_every_post() {
+        let capsule = TestCapsule::new("fingerprint-entries");
+        let before = capsule.fingerprints();
+
+        capsule.write_post("third", "Third", "The third post.");
+        let added = capsule.fingerprints();
+
+        assert_ne!(before["first"], added["first"]);
+        assert_ne!(before["second"], added["second"]);
+
+        capsule.write_post("third", "Retitled", "The third post.");
+        let retitled = capsule.fingerprints();
+
+        assert_ne!(added["first"], retitled["first"]);
+        assert_ne!(added["second"], retitled["second"]);
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use eyre::WrapErr;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// Bump this whenever the format of the manifest or the way we compute fingerprints changes, so
+// that manifests from older versions of gempost are ignored.
+const MANIFEST_VERSION: u32 = 1;
+
+/// A record of a post page generated by a previous build.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedPost {
+    /// The path of the generated post page.
+    pub output: PathBuf,
+
+    /// A hash of all the inputs used to render the post page.
+    pub fingerprint: String,
+}
+
+/// A manifest of the post pages generated by the previous build, used to skip re-rendering posts
+/// that haven't changed.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    version: u32,
+
+    /// Cached posts, keyed by the path of their gemtext source file.
+    pub posts: BTreeMap<PathBuf, CachedPost>,
+}
+
+impl Manifest {
+    pub fn new() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            posts: BTreeMap::new(),
+        }
+    }
+
+    /// Read the manifest from the previous build.
+    ///
+    /// This returns `None` if there is no manifest or it can't be used, in which case we need to
+    /// do a full rebuild.
+    pub fn read(path: &Path, warn_handler: impl Fn(&str)) -> eyre::Result<Option<Self>> {
+        let manifest_file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).wrap_err("failed opening build cache file"),
+        };
+
+        let manifest: Self = match serde_json::from_reader(manifest_file) {
+            Ok(manifest) => manifest,
+            Err(_) => {
+                warn_handler(&format!(
+                    "The build cache file is corrupt, so gempost will rebuild everything: {}",
+                    path.to_string_lossy()
+                ));
+                return Ok(None);
+            }
+        };
+
+        if manifest.version != MANIFEST_VERSION {
+            return Ok(None);
+        }
+
+        Ok(Some(manifest))
+    }
+
+    pub fn write(&self, path: &Path) -> eyre::Result<()> {
+        let contents =
+            serde_json::to_string_pretty(self).wrap_err("failed serializing build cache")?;
+
+        fs::write(path, contents).wrap_err("failed writing build cache file")?;
+
+        Ok(())
+    }
+
+    /// Whether the post generated from this source file is unchanged since the previous build and
+    /// its generated page still exists.
+    pub fn is_fresh(&self, source: &Path, post: &CachedPost) -> bool {
+        match self.posts.get(source) {
+            Some(cached) => cached == post && post.output.is_file(),
+            None => false,
+        }
+    }
+}
+
+/// A hasher for computing the fingerprint of the inputs to a generated page.
+#[derive(Debug, Default)]
+pub struct Fingerprint {
+    hasher: Sha256,
+}
+
+impl Fingerprint {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, data: impl AsRef<[u8]>) {
+        let data = data.as_ref();
+
+        // Include the length of each input so that different sequences of inputs can't produce
+        // the same fingerprint.
+        self.hasher.update((data.len() as u64).to_le_bytes());
+        self.hasher.update(data);
+    }
+
+    pub fn finish(self) -> String {
+        self.hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+}
@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 
+use chrono::NaiveDate;
 use clap::{Args, Parser, Subcommand};
 
 #[derive(Parser, Clone)]
@@ -7,14 +8,26 @@ use clap::{Args, Parser, Subcommand};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Print every file as it's generated
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
 }
 
 #[derive(Args, Clone)]
 pub struct Init {
     /// The directory to create the new project in
     ///
-    /// This will not overwrite any files already in the directory.
+    /// This will not overwrite any files already in the directory unless `--force` is passed.
     pub directory: Option<PathBuf>,
+
+    /// Overwrite any of the example files which already exist
+    #[arg(short, long)]
+    pub force: bool,
 }
 
 #[derive(Args, Clone)]
@@ -22,6 +35,109 @@ pub struct Build {
     /// The path of the gempost config file
     #[arg(short, long, value_name = "PATH", default_value = "./gempost.yaml")]
     pub config: PathBuf,
+
+    /// Use the settings from this profile in the config file
+    ///
+    /// Each setting in the profile replaces the same setting in the rest of the config file. Settings
+    /// which are mappings, like `author`, are replaced as a whole rather than merged.
+    #[arg(short, long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// The directory to generate the capsule in
+    ///
+    /// This overrides the `public_dir` in the config file.
+    #[arg(short, long, value_name = "DIR")]
+    pub output: Option<PathBuf>,
+
+    /// Build the capsule for this URL, overriding the `url` in the config file
+    ///
+    /// Every generated link, including the post URLs and the feed URLs, uses this URL instead. This
+    /// is useful for building a mirror of your capsule on another host.
+    #[arg(long, value_name = "URL")]
+    pub capsule_url: Option<String>,
+
+    /// Write a JSON report of the build to this file
+    ///
+    /// This includes the number of posts, every file that was generated, and any warnings.
+    #[arg(long, value_name = "PATH")]
+    pub report: Option<PathBuf>,
+
+    /// Include draft posts in the build
+    ///
+    /// This is useful for previewing drafts locally.
+    #[arg(long)]
+    pub drafts: bool,
+
+    /// Include expired posts in the build
+    ///
+    /// This is useful for archiving posts which have an `expires` time.
+    #[arg(long)]
+    pub ignore_expiry: bool,
+
+    /// Only include posts published on or after this date, like `2023-01-01`
+    ///
+    /// Posts without a `published` time are left out, unless `--include-unpublished` is passed.
+    #[arg(long, value_name = "DATE")]
+    pub since: Option<NaiveDate>,
+
+    /// Only include posts published on or before this date, like `2023-12-31`
+    ///
+    /// Posts without a `published` time are left out, unless `--include-unpublished` is passed.
+    #[arg(long, value_name = "DATE")]
+    pub until: Option<NaiveDate>,
+
+    /// Keep posts without a `published` time when using `--since` or `--until`
+    #[arg(long)]
+    pub include_unpublished: bool,
+
+    /// Re-render every post, even if it hasn't changed since the last build
+    #[arg(short, long)]
+    pub force: bool,
+
+    /// Warn instead of failing when a post's `published` time is after its `updated` time
+    #[arg(long)]
+    pub lenient_dates: bool,
+
+    /// Fail if the build has any warnings, like a post missing its gemtext or YAML metadata file
+    ///
+    /// Every warning is reported together. This also checks that preformatted blocks in posts have
+    /// alt text.
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Print the files that would be generated without writing anything
+    ///
+    /// Templates are still rendered, so this catches template errors.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Print the post and metadata files that would be read, then exit without building
+    ///
+    /// This is useful for checking the `posts_include` and `posts_ignore` in the config file.
+    #[arg(long, conflicts_with = "watch")]
+    pub dump_sources: bool,
+
+    /// Check that links between pages in your capsule aren't broken
+    ///
+    /// This checks every link in the generated gemtext pages which points somewhere within your
+    /// capsule. Links to other capsules and websites are not checked.
+    #[arg(long)]
+    pub check_links: bool,
+
+    /// Keep running and rebuild whenever a post, template, or static file changes
+    #[arg(short, long)]
+    pub watch: bool,
+}
+
+#[derive(Args, Clone)]
+pub struct Validate {
+    /// The path of the gempost config file
+    #[arg(short, long, value_name = "PATH", default_value = "./gempost.yaml")]
+    pub config: PathBuf,
+
+    /// Use the settings from this profile in the config file
+    #[arg(short, long, value_name = "NAME")]
+    pub profile: Option<String>,
 }
 
 #[derive(Args, Clone)]
@@ -36,6 +152,57 @@ pub struct New {
     /// The path of the gempost config file
     #[arg(short, long, value_name = "PATH", default_value = "./gempost.yaml")]
     pub config: PathBuf,
+
+    /// Use the settings from this profile in the config file
+    #[arg(short, long, value_name = "NAME")]
+    pub profile: Option<String>,
+}
+
+#[derive(Args, Clone)]
+pub struct Render {
+    /// The gemtext or Markdown file of the post to render, or `-` to read it from stdin
+    pub post: PathBuf,
+
+    /// The YAML metadata file of the post
+    ///
+    /// This defaults to the `.yaml` file next to the post. Without one, the metadata comes from the
+    /// front matter at the top of the post.
+    #[arg(short, long, value_name = "PATH")]
+    pub metadata: Option<PathBuf>,
+
+    /// The file to write the post page to, instead of stdout
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// The path of the gempost config file
+    #[arg(short, long, value_name = "PATH", default_value = "./gempost.yaml")]
+    pub config: PathBuf,
+
+    /// Use the settings from this profile in the config file
+    #[arg(short, long, value_name = "NAME")]
+    pub profile: Option<String>,
+}
+
+#[derive(Args, Clone)]
+pub struct RenderTemplate {
+    /// The Tera template to render
+    #[arg(short, long, value_name = "PATH")]
+    pub template: PathBuf,
+
+    /// The YAML metadata file of the post to render the template with
+    ///
+    /// This has the same format as the metadata files in the posts directory. The body of the post
+    /// comes from the `.gmi` file next to it, if there is one.
+    #[arg(short, long, value_name = "PATH")]
+    pub fixture: PathBuf,
+
+    /// The path of the gempost config file
+    #[arg(short, long, value_name = "PATH", default_value = "./gempost.yaml")]
+    pub config: PathBuf,
+
+    /// Use the settings from this profile in the config file
+    #[arg(short, long, value_name = "NAME")]
+    pub profile: Option<String>,
 }
 
 #[derive(Subcommand, Clone)]
@@ -50,8 +217,27 @@ pub enum Commands {
     /// This builds the gempost project in your current working directory.
     Build(Build),
 
+    /// Check your posts and templates for problems without building your capsule
+    ///
+    /// This reads every post, including drafts and expired posts, and renders every post page
+    /// without writing any files. It reports every problem it finds rather than stopping at the
+    /// first one.
+    Validate(Validate),
+
     /// Create a new post
     ///
     /// This generates an empty gemtext file and YAML metadata file, automatically assigning a post ID.
     New(New),
+
+    /// Render the page for a single post
+    ///
+    /// This uses the templates in the config file, but the post doesn't need to be in the posts
+    /// directory, and no other posts are read. The post is rendered even if it's a draft.
+    Render(Render),
+
+    /// Render a template with the data for a fixture post
+    ///
+    /// This is for trying out templates without any posts. The template gets the same data it
+    /// would for a post page, and the result is printed to stdout.
+    RenderTemplate(RenderTemplate),
 }
@@ -1,8 +1,12 @@
+use std::collections::BTreeMap;
+use std::fmt;
 use std::io;
 use std::path::PathBuf;
 use std::{fs::File, path::Path};
 
+use chrono::{DateTime, FixedOffset, Offset, Utc};
 use eyre::{bail, WrapErr};
+use globset::{GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
 use serde::Deserialize;
 use url::Url;
 
@@ -15,6 +19,127 @@ pub struct RawAuthorConfig {
     pub uri: Option<String>,
 }
 
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct RawAssetConfig {
+    pub dir: PathBuf,
+    pub path: String,
+    pub glob: Option<String>,
+}
+
+/// The type of a custom value in a post's metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    String,
+    Number,
+    Bool,
+    List,
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ValueType::String => "string",
+            ValueType::Number => "number",
+            ValueType::Bool => "bool",
+            ValueType::List => "list",
+        })
+    }
+}
+
+/// A feed of only the posts in some categories, with its own Atom feed and index page.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SubfeedConfig {
+    /// The title of the feed, which defaults to the title of the capsule.
+    pub title: Option<String>,
+
+    /// The categories a post needs at least one of to be in this feed.
+    pub categories: Vec<String>,
+
+    /// The URL path of the Atom feed.
+    pub feed_path: String,
+
+    /// The URL path of the index page.
+    pub index_path: String,
+}
+
+/// The custom values that every post's metadata must have.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct ValuesSchema {
+    /// The values every post must have, along with their types.
+    #[serde(default)]
+    pub required: BTreeMap<String, ValueType>,
+
+    /// Whether to reject values which aren't in the schema.
+    #[serde(default)]
+    pub strict: bool,
+}
+
+/// The metadata field to sort posts by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    /// The publish time, or the last updated time if there is no publish time.
+    #[default]
+    Published,
+    Updated,
+    Title,
+    Id,
+}
+
+/// What to do with posts that don't have a `published` date in the syndication feeds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MissingPublished {
+    /// Leave out the published date.
+    #[default]
+    Omit,
+    /// Use the updated date as the published date.
+    Updated,
+    /// Leave the post out of the feeds.
+    Exclude,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    Ascending,
+    #[default]
+    Descending,
+}
+
+/// Where on a page to put the link to its feed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedLinkPosition {
+    Top,
+    #[default]
+    Bottom,
+}
+
+/// A link to the feed which is added to every index page and post page.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct FeedLinkConfig {
+    /// The text of the link.
+    #[serde(default = "defaults::feed_link_text")]
+    pub text: String,
+
+    /// Whether the link goes at the top or the bottom of the page.
+    #[serde(default)]
+    pub position: FeedLinkPosition,
+}
+
+/// An external command to run as part of the build, like after the capsule is built.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum PostBuildCommand {
+    /// A command line, which is run with `sh -c`.
+    Shell(String),
+
+    /// A program followed by its arguments, which is run directly.
+    Args(Vec<String>),
+}
+
 #[derive(Debug, PartialEq, Eq, Deserialize)]
 struct RawConfig {
     #[serde(default = "defaults::public_dir")]
@@ -23,20 +148,91 @@ struct RawConfig {
     static_dir: PathBuf,
     #[serde(default = "defaults::posts_dir")]
     posts_dir: PathBuf,
-    #[serde(default = "defaults::index_template_file")]
-    index_template_file: PathBuf,
-    #[serde(default = "defaults::post_template_file")]
-    post_template_file: PathBuf,
-    #[serde(default = "defaults::post_path")]
-    post_path: String,
-    #[serde(default = "defaults::index_path")]
-    index_path: String,
+    #[serde(default)]
+    posts_include: Vec<String>,
+    #[serde(default)]
+    posts_ignore: Vec<String>,
+    #[serde(default = "defaults::cache_file")]
+    cache_file: PathBuf,
+    theme: Option<String>,
+    index_template_file: Option<PathBuf>,
+    post_template_file: Option<PathBuf>,
+    category_template_file: Option<PathBuf>,
+    categories_template_file: Option<PathBuf>,
+    redirect_template_file: Option<PathBuf>,
+    archive_template_file: Option<PathBuf>,
+    feed_template_file: Option<PathBuf>,
+    robots_template_file: Option<PathBuf>,
+    security_template_file: Option<PathBuf>,
+    includes_dir: Option<PathBuf>,
+    snippets_dir: Option<PathBuf>,
+    post_path: Option<String>,
+    #[serde(default = "defaults::gemtext_extension")]
+    gemtext_extension: String,
+    #[serde(default)]
+    pretty_urls: bool,
+    index_path: Option<String>,
+    index_page_path: Option<String>,
+    per_page: Option<usize>,
     #[serde(default = "defaults::feed_path")]
     feed_path: String,
+    category_path: Option<String>,
+    categories_path: Option<String>,
+    archive_path: Option<String>,
+    rss_path: Option<String>,
+    gemsub_path: Option<String>,
+    json_feed_path: Option<String>,
+    feed_limit: Option<usize>,
+    feed_full_content: Option<usize>,
+    #[serde(default)]
+    sort: SortKey,
+    #[serde(default)]
+    sort_direction: SortDirection,
+    #[serde(default)]
+    missing_published: MissingPublished,
+    #[serde(default = "defaults::words_per_minute")]
+    words_per_minute: u32,
+    #[serde(default = "defaults::updated_threshold_days")]
+    updated_threshold_days: u32,
+    #[serde(default = "defaults::summary_length")]
+    summary_length: usize,
+    #[serde(default)]
+    plain_summaries: bool,
+    #[serde(default = "defaults::more_marker")]
+    more_marker: String,
+    #[serde(default)]
+    inline_links: bool,
+    #[serde(default)]
+    normalize_whitespace: bool,
+    wrap_width: Option<usize>,
+    #[serde(default)]
+    skip_unchanged_writes: bool,
+    #[serde(default = "defaults::breadcrumb_home")]
+    breadcrumb_home: String,
+    feed_link: Option<FeedLinkConfig>,
+    #[serde(default)]
+    obfuscate_emails: bool,
+    #[serde(default)]
+    assets: Vec<RawAssetConfig>,
+    values_schema: Option<ValuesSchema>,
+    #[serde(default)]
+    subfeeds: BTreeMap<String, SubfeedConfig>,
+    post_build: Option<PostBuildCommand>,
+    #[serde(default)]
+    feed_checksums: bool,
+    sign_command: Option<PostBuildCommand>,
     title: String,
     url: String,
     subtitle: Option<String>,
     rights: Option<String>,
+    icon: Option<String>,
+    logo: Option<String>,
+    lang: Option<String>,
+    category_scheme: Option<String>,
+    default_timezone: Option<String>,
+    #[serde(default)]
+    updated_from_mtime: bool,
+    feed_updated: Option<String>,
     author: Option<RawAuthorConfig>,
 }
 
@@ -55,6 +251,14 @@ mod defaults {
         PathBuf::from("./posts/")
     }
 
+    pub fn cache_file() -> PathBuf {
+        PathBuf::from("./.gempost-cache.json")
+    }
+
+    pub fn themes_dir() -> PathBuf {
+        PathBuf::from("./themes/")
+    }
+
     pub fn index_template_file() -> PathBuf {
         PathBuf::from("./templates/index.tera")
     }
@@ -63,21 +267,105 @@ mod defaults {
         PathBuf::from("./templates/post.tera")
     }
 
-    pub fn post_path() -> String {
-        String::from("/posts/{{ slug }}.gmi")
+    pub fn gemtext_extension() -> String {
+        String::from("gmi")
     }
 
-    pub fn index_path() -> String {
-        String::from("/posts/index.gmi")
+    pub fn post_path(ext: &str) -> String {
+        format!("/posts/{{{{ slug }}}}.{ext}")
+    }
+
+    pub fn index_path(ext: &str) -> String {
+        format!("/posts/index.{ext}")
+    }
+
+    pub fn index_page_path(ext: &str) -> String {
+        format!("/posts/page/{{{{ page }}}}.{ext}")
     }
 
     pub fn feed_path() -> String {
         String::from("/posts/atom.xml")
     }
+
+    pub fn category_path(ext: &str) -> String {
+        format!("/categories/{{{{ slug }}}}.{ext}")
+    }
+
+    pub fn categories_path(ext: &str) -> String {
+        format!("/categories/index.{ext}")
+    }
+
+    pub fn archive_path(ext: &str) -> String {
+        format!("/posts/all-posts.{ext}")
+    }
+
+    pub fn words_per_minute() -> u32 {
+        200
+    }
+
+    pub fn updated_threshold_days() -> u32 {
+        1
+    }
+
+    pub fn summary_length() -> usize {
+        200
+    }
+
+    pub fn more_marker() -> String {
+        String::from("<!-- more -->")
+    }
+
+    pub fn breadcrumb_home() -> String {
+        String::from("Home")
+    }
+
+    pub fn feed_link_text() -> String {
+        String::from("Subscribe (Atom)")
+    }
+}
+
+// The key in the config file which holds the profiles.
+const PROFILES_KEY: &str = "profiles";
+
+// Replace the top-level keys in the base config with the ones in the profile.
+fn apply_profile(
+    mut base: serde_yaml::Mapping,
+    profile: &str,
+) -> Result<serde_yaml::Mapping, String> {
+    let mut profiles = match base.remove(PROFILES_KEY) {
+        Some(serde_yaml::Value::Mapping(profiles)) => profiles,
+        Some(_) => return Err(format!("The `{PROFILES_KEY}` must be a mapping.")),
+        None => serde_yaml::Mapping::new(),
+    };
+
+    let overrides = match profiles.remove(profile) {
+        Some(serde_yaml::Value::Mapping(overrides)) => overrides,
+        Some(_) => return Err(format!("The `{profile}` profile must be a mapping.")),
+        None => {
+            let names = profiles
+                .keys()
+                .filter_map(serde_yaml::Value::as_str)
+                .map(|name| format!("`{name}`"))
+                .collect::<Vec<_>>();
+
+            return Err(if names.is_empty() {
+                format!("There is no `{profile}` profile, because there are no `{PROFILES_KEY}`.")
+            } else {
+                format!(
+                    "There is no `{profile}` profile. The profiles are: {}",
+                    names.join(", ")
+                )
+            });
+        }
+    };
+
+    base.extend(overrides);
+
+    Ok(base)
 }
 
 impl RawConfig {
-    fn read(path: &Path) -> eyre::Result<Self> {
+    fn read(path: &Path, profile: Option<&str>) -> eyre::Result<Self> {
         let config_file = match File::open(path) {
             Ok(file) => file,
             Err(err) if err.kind() == io::ErrorKind::NotFound => {
@@ -88,12 +376,30 @@ impl RawConfig {
             Err(err) => bail!(err),
         };
 
-        match serde_yaml::from_reader(config_file) {
+        let invalid = |reason: String| Error::InvalidConfigFile {
+            path: path.to_owned(),
+            reason,
+        };
+
+        let mut config = match serde_yaml::from_reader(config_file) {
+            Ok(serde_yaml::Value::Mapping(config)) => config,
+            Ok(_) => bail!(invalid(String::from(
+                "The config file must be a mapping of settings."
+            ))),
+            Err(err) => bail!(invalid(err.to_string())),
+        };
+
+        config = match profile {
+            Some(profile) => apply_profile(config, profile).map_err(invalid)?,
+            None => {
+                config.remove(PROFILES_KEY);
+                config
+            }
+        };
+
+        match serde_yaml::from_value(serde_yaml::Value::Mapping(config)) {
             Ok(config) => Ok(config),
-            Err(err) => bail!(Error::InvalidConfigFile {
-                path: path.to_owned(),
-                reason: err.to_string(),
-            }),
+            Err(err) => bail!(invalid(err.to_string())),
         }
     }
 }
@@ -115,41 +421,597 @@ impl From<RawAuthorConfig> for AuthorConfig {
     }
 }
 
+/// A directory of static files to copy into the public directory.
+#[derive(Debug, Clone)]
+pub struct AssetConfig {
+    /// The directory to copy files from.
+    pub dir: PathBuf,
+
+    /// The URL path to copy files to.
+    pub path: String,
+
+    /// If set, only files whose path relative to `dir` matches this glob are copied.
+    pub glob: Option<GlobMatcher>,
+}
+
+impl AssetConfig {
+    fn from_raw(raw: RawAssetConfig) -> Result<Self, String> {
+        let glob = match raw.glob {
+            Some(pattern) => Some(
+                GlobBuilder::new(&pattern)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|err| format!("The asset glob `{pattern}` is invalid. {err}"))?
+                    .compile_matcher(),
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            dir: raw.dir,
+            path: raw.path,
+            glob,
+        })
+    }
+}
+
+/// Which files in the posts directory are read as posts.
+#[derive(Debug, Clone)]
+pub struct PostFilesConfig {
+    /// If set, only files whose name matches one of these globs are read.
+    pub include: Option<GlobSet>,
+
+    /// Files whose name matches one of these globs are skipped.
+    pub ignore: GlobSet,
+}
+
+// Compile a list of globs from the config file into a set.
+fn build_glob_set(key: &str, patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+
+    for pattern in patterns {
+        builder.add(
+            GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(|err| format!("The glob `{pattern}` in `{key}` is invalid. {err}"))?,
+        );
+    }
+
+    builder
+        .build()
+        .map_err(|err| format!("The globs in `{key}` are invalid. {err}"))
+}
+
+impl PostFilesConfig {
+    fn from_raw(include: &[String], ignore: &[String]) -> Result<Self, String> {
+        Ok(Self {
+            include: if include.is_empty() {
+                None
+            } else {
+                Some(build_glob_set("posts_include", include)?)
+            },
+            ignore: build_glob_set("posts_ignore", ignore)?,
+        })
+    }
+
+    /// Whether a file or directory in the posts directory should be read, by its name.
+    ///
+    /// Hidden files and directories, whose names start with a dot, are always skipped.
+    pub fn is_selected(&self, file_name: &Path) -> bool {
+        if file_name.to_string_lossy().starts_with('.') {
+            return false;
+        }
+
+        if self
+            .include
+            .as_ref()
+            .is_some_and(|include| !include.is_match(file_name))
+        {
+            return false;
+        }
+
+        !self.ignore.is_match(file_name)
+    }
+}
+
+// The files in a theme directory which are used in place of each template setting, unless that
+// setting is in the config.
+mod theme_files {
+    pub const INDEX_TEMPLATE: &str = "index.tera";
+    pub const POST_TEMPLATE: &str = "post.tera";
+    pub const CATEGORY_TEMPLATE: &str = "category.tera";
+    pub const CATEGORIES_TEMPLATE: &str = "categories.tera";
+    pub const REDIRECT_TEMPLATE: &str = "redirect.tera";
+    pub const ARCHIVE_TEMPLATE: &str = "archive.tera";
+    pub const FEED_TEMPLATE: &str = "feed.tera";
+    pub const ROBOTS_TEMPLATE: &str = "robots.tera";
+    pub const SECURITY_TEMPLATE: &str = "security.tera";
+    pub const INCLUDES_DIR: &str = "includes";
+}
+
+// A theme is either the name of a directory in the themes directory or a path to a directory.
+fn theme_dir(theme: &str) -> PathBuf {
+    if theme.contains(['/', '\\']) {
+        PathBuf::from(theme)
+    } else {
+        defaults::themes_dir().join(theme)
+    }
+}
+
 #[derive(Debug)]
 pub struct Config {
     pub public_dir: PathBuf,
     pub static_dir: PathBuf,
     pub posts_dir: PathBuf,
+    /// Which files in the posts directory are read as posts.
+    pub post_files: PostFilesConfig,
+    pub cache_file: PathBuf,
     pub index_template_file: PathBuf,
     pub post_template_file: PathBuf,
+    pub category_template_file: Option<PathBuf>,
+    pub categories_template_file: Option<PathBuf>,
+    pub redirect_template_file: Option<PathBuf>,
+    pub archive_template_file: Option<PathBuf>,
+    pub feed_template_file: Option<PathBuf>,
+    /// The template for the `robots.txt` at the root of the capsule, if there is one.
+    pub robots_template_file: Option<PathBuf>,
+    /// The template for the `.well-known/security.txt` of the capsule, if there is one.
+    pub security_template_file: Option<PathBuf>,
+    pub includes_dir: Option<PathBuf>,
+    /// The directory that `@include` lines in posts are resolved against, if posts can include
+    /// snippets.
+    pub snippets_dir: Option<PathBuf>,
     pub post_path: String,
+    /// The file extension of generated gemtext pages, without the leading dot.
+    pub gemtext_extension: String,
+    pub pretty_urls: bool,
     pub index_path: String,
+    pub index_page_path: String,
+    pub per_page: Option<usize>,
     pub feed_path: String,
+    pub category_path: String,
+    pub categories_path: String,
+    pub archive_path: String,
+    pub rss_path: Option<String>,
+    pub gemsub_path: Option<String>,
+    pub json_feed_path: Option<String>,
+    pub feed_limit: Option<usize>,
+    /// How many of the most recent posts in the Atom feed have their full content, rather than
+    /// just a summary. If this isn't set, every post does.
+    pub feed_full_content: Option<usize>,
+    pub sort: SortKey,
+    pub sort_direction: SortDirection,
+    pub missing_published: MissingPublished,
+    pub words_per_minute: u32,
+    /// How many days after a post was published it has to be updated to count as recently
+    /// updated.
+    pub updated_threshold_days: u32,
+    pub summary_length: usize,
+    pub plain_summaries: bool,
+    pub more_marker: String,
+    pub inline_links: bool,
+    /// Whether to tidy up the whitespace in generated gemtext pages.
+    pub normalize_whitespace: bool,
+    /// The width to hard-wrap long lines of text in generated gemtext pages at, if any.
+    pub wrap_width: Option<usize>,
+    /// Whether to leave generated files alone when their contents haven't changed, so their
+    /// modification times are preserved.
+    pub skip_unchanged_writes: bool,
+    /// The name of the capsule's homepage in the breadcrumbs of each post.
+    pub breadcrumb_home: String,
+    /// The link to the feed to add to every index page and post page, if there is one.
+    pub feed_link: Option<FeedLinkConfig>,
+    /// Whether to obfuscate author email addresses in gemtext pages, but not the syndication feeds.
+    pub obfuscate_emails: bool,
+    pub assets: Vec<AssetConfig>,
+    pub values_schema: Option<ValuesSchema>,
+    /// Feeds of only the posts in some categories, by name.
+    pub subfeeds: BTreeMap<String, SubfeedConfig>,
+    pub post_build: Option<PostBuildCommand>,
+    /// Whether to write a file with the SHA-256 checksum of each feed and index page next to it.
+    pub feed_checksums: bool,
+    /// The command to sign each feed and index page with, if they should be signed.
+    pub sign_command: Option<PostBuildCommand>,
     pub title: String,
     pub url: Url,
     pub subtitle: Option<String>,
     pub rights: Option<String>,
+    /// A small image for the feed, which may be relative to the capsule URL.
+    pub icon: Option<String>,
+    /// A larger image for the feed, which may be relative to the capsule URL.
+    pub logo: Option<String>,
+    pub lang: Option<String>,
+    /// The URI which identifies the categorization scheme of post categories in the feeds.
+    pub category_scheme: Option<String>,
+    /// The offset to interpret dates in post metadata files at, when they don't have a time.
+    pub default_timezone: FixedOffset,
+    /// Whether to use the modification time of a post when its metadata file has no `updated` time.
+    pub updated_from_mtime: bool,
+    /// A fixed time to use as the `updated` time of every feed, instead of when the most recent post
+    /// in it was updated.
+    pub feed_updated: Option<DateTime<FixedOffset>>,
     pub author: Option<AuthorConfig>,
 }
 
 impl Config {
-    pub fn read(path: &Path) -> eyre::Result<Self> {
-        let raw = RawConfig::read(path).wrap_err("failed reading config file")?;
+    /// Read the config file, with the settings from the named profile in place of the base ones.
+    pub fn read(path: &Path, profile: Option<&str>) -> eyre::Result<Self> {
+        let raw = RawConfig::read(path, profile).wrap_err("failed reading config file")?;
+
+        if raw.words_per_minute == 0 {
+            bail!(Error::InvalidConfigFile {
+                path: path.to_owned(),
+                reason: String::from("The `words_per_minute` must be greater than zero."),
+            });
+        }
+
+        if raw.per_page == Some(0) {
+            bail!(Error::InvalidConfigFile {
+                path: path.to_owned(),
+                reason: String::from("The `per_page` must be greater than zero."),
+            });
+        }
+
+        if raw.feed_limit == Some(0) {
+            bail!(Error::InvalidConfigFile {
+                path: path.to_owned(),
+                reason: String::from("The `feed_limit` must be greater than zero."),
+            });
+        }
+
+        if raw.wrap_width == Some(0) {
+            bail!(Error::InvalidConfigFile {
+                path: path.to_owned(),
+                reason: String::from("The `wrap_width` must be greater than zero."),
+            });
+        }
+
+        if raw.more_marker.trim().is_empty() {
+            bail!(Error::InvalidConfigFile {
+                path: path.to_owned(),
+                reason: String::from("The `more_marker` cannot be empty."),
+            });
+        }
+
+        let is_empty_command = |command: &Option<PostBuildCommand>| match command {
+            Some(PostBuildCommand::Shell(command)) => command.trim().is_empty(),
+            Some(PostBuildCommand::Args(args)) => args.is_empty(),
+            None => false,
+        };
+
+        if is_empty_command(&raw.post_build) {
+            bail!(Error::InvalidConfigFile {
+                path: path.to_owned(),
+                reason: String::from("The `post_build` command cannot be empty."),
+            });
+        }
+
+        if is_empty_command(&raw.sign_command) {
+            bail!(Error::InvalidConfigFile {
+                path: path.to_owned(),
+                reason: String::from("The `sign_command` cannot be empty."),
+            });
+        }
+
+        if let Some(name) = raw
+            .subfeeds
+            .iter()
+            .find(|(_, subfeed)| subfeed.categories.is_empty())
+            .map(|(name, _)| name)
+        {
+            bail!(Error::InvalidConfigFile {
+                path: path.to_owned(),
+                reason: format!("The `{name}` subfeed must have at least one category."),
+            });
+        }
+
+        // Accept the extension with or without the leading dot.
+        let gemtext_extension = raw.gemtext_extension.trim_start_matches('.').to_owned();
+
+        if gemtext_extension.is_empty() || gemtext_extension.contains(['/', '.']) {
+            bail!(Error::InvalidConfigFile {
+                path: path.to_owned(),
+                reason: format!(
+                    "The `gemtext_extension` must be a file extension like `gmi`, not `{}`.",
+                    raw.gemtext_extension
+                ),
+            });
+        }
+
+        let theme_dir = raw.theme.as_deref().map(theme_dir);
+
+        if let Some(theme_dir) = &theme_dir {
+            if !theme_dir.is_dir() {
+                bail!(Error::InvalidConfigFile {
+                    path: path.to_owned(),
+                    reason: format!(
+                        "The `theme` directory does not exist: `{}`",
+                        theme_dir.to_string_lossy()
+                    ),
+                });
+            }
+
+            // The index and post templates are the only ones every capsule needs.
+            let missing_templates = [
+                (&raw.index_template_file, theme_files::INDEX_TEMPLATE),
+                (&raw.post_template_file, theme_files::POST_TEMPLATE),
+            ]
+            .into_iter()
+            .filter(|(setting, name)| setting.is_none() && !theme_dir.join(name).is_file())
+            .map(|(_, name)| format!("- {name}"))
+            .collect::<Vec<_>>();
+
+            if !missing_templates.is_empty() {
+                bail!(Error::InvalidConfigFile {
+                    path: path.to_owned(),
+                    reason: format!(
+                        "The theme at `{}` is missing these templates:\n{}",
+                        theme_dir.to_string_lossy(),
+                        missing_templates.join("\n")
+                    ),
+                });
+            }
+        }
+
+        // Templates which aren't in the config come from the theme, if it has them.
+        let theme_file = |name: &str| {
+            theme_dir
+                .as_ref()
+                .map(|theme_dir| theme_dir.join(name))
+                .filter(|path| path.exists())
+        };
+
+        // Dates without a time are interpreted as midnight UTC by default.
+        let default_timezone = match &raw.default_timezone {
+            Some(offset) => offset.parse::<FixedOffset>().map_err(|_| Error::InvalidConfigFile {
+                path: path.to_owned(),
+                reason: format!(
+                    "The `default_timezone` must be a UTC offset like `+00:00` or `-05:00`, not `{offset}`."
+                ),
+            })?,
+            None => Utc.fix(),
+        };
+
+        let feed_updated = match &raw.feed_updated {
+            Some(updated) => Some(DateTime::parse_from_rfc3339(updated).map_err(|_| {
+                Error::InvalidConfigFile {
+                    path: path.to_owned(),
+                    reason: format!(
+                        "The `feed_updated` must be an RFC 3339 timestamp like `2024-01-01T00:00:00Z`, not `{updated}`."
+                    ),
+                }
+            })?),
+            None => None,
+        };
+
+        let post_files =
+            PostFilesConfig::from_raw(&raw.posts_include, &raw.posts_ignore).map_err(|reason| {
+                Error::InvalidConfigFile {
+                    path: path.to_owned(),
+                    reason,
+                }
+            })?;
+
+        let assets = raw
+            .assets
+            .into_iter()
+            .map(AssetConfig::from_raw)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|reason| Error::InvalidConfigFile {
+                path: path.to_owned(),
+                reason,
+            })?;
 
         Ok(Self {
             public_dir: raw.public_dir,
             static_dir: raw.static_dir,
             posts_dir: raw.posts_dir,
-            index_template_file: raw.index_template_file,
-            post_template_file: raw.post_template_file,
-            post_path: raw.post_path,
-            index_path: raw.index_path,
+            post_files,
+            cache_file: raw.cache_file,
+            index_template_file: raw
+                .index_template_file
+                .or_else(|| theme_file(theme_files::INDEX_TEMPLATE))
+                .unwrap_or_else(defaults::index_template_file),
+            post_template_file: raw
+                .post_template_file
+                .or_else(|| theme_file(theme_files::POST_TEMPLATE))
+                .unwrap_or_else(defaults::post_template_file),
+            category_template_file: raw
+                .category_template_file
+                .or_else(|| theme_file(theme_files::CATEGORY_TEMPLATE)),
+            categories_template_file: raw
+                .categories_template_file
+                .or_else(|| theme_file(theme_files::CATEGORIES_TEMPLATE)),
+            redirect_template_file: raw
+                .redirect_template_file
+                .or_else(|| theme_file(theme_files::REDIRECT_TEMPLATE)),
+            archive_template_file: raw
+                .archive_template_file
+                .or_else(|| theme_file(theme_files::ARCHIVE_TEMPLATE)),
+            feed_template_file: raw
+                .feed_template_file
+                .or_else(|| theme_file(theme_files::FEED_TEMPLATE)),
+            robots_template_file: raw
+                .robots_template_file
+                .or_else(|| theme_file(theme_files::ROBOTS_TEMPLATE)),
+            security_template_file: raw
+                .security_template_file
+                .or_else(|| theme_file(theme_files::SECURITY_TEMPLATE)),
+            includes_dir: raw
+                .includes_dir
+                .or_else(|| theme_file(theme_files::INCLUDES_DIR)),
+            snippets_dir: raw.snippets_dir,
+            post_path: raw
+                .post_path
+                .unwrap_or_else(|| defaults::post_path(&gemtext_extension)),
+            gemtext_extension: gemtext_extension.clone(),
+            pretty_urls: raw.pretty_urls,
+            index_path: raw
+                .index_path
+                .unwrap_or_else(|| defaults::index_path(&gemtext_extension)),
+            index_page_path: raw
+                .index_page_path
+                .unwrap_or_else(|| defaults::index_page_path(&gemtext_extension)),
+            per_page: raw.per_page,
             feed_path: raw.feed_path,
+            category_path: raw
+                .category_path
+                .unwrap_or_else(|| defaults::category_path(&gemtext_extension)),
+            categories_path: raw
+                .categories_path
+                .unwrap_or_else(|| defaults::categories_path(&gemtext_extension)),
+            archive_path: raw
+                .archive_path
+                .unwrap_or_else(|| defaults::archive_path(&gemtext_extension)),
+            rss_path: raw.rss_path,
+            gemsub_path: raw.gemsub_path,
+            json_feed_path: raw.json_feed_path,
+            feed_limit: raw.feed_limit,
+            feed_full_content: raw.feed_full_content,
+            sort: raw.sort,
+            sort_direction: raw.sort_direction,
+            missing_published: raw.missing_published,
+            words_per_minute: raw.words_per_minute,
+            updated_threshold_days: raw.updated_threshold_days,
+            summary_length: raw.summary_length,
+            plain_summaries: raw.plain_summaries,
+            more_marker: raw.more_marker,
+            inline_links: raw.inline_links,
+            normalize_whitespace: raw.normalize_whitespace,
+            wrap_width: raw.wrap_width,
+            skip_unchanged_writes: raw.skip_unchanged_writes,
+            breadcrumb_home: raw.breadcrumb_home,
+            feed_link: raw.feed_link,
+            obfuscate_emails: raw.obfuscate_emails,
+            assets,
+            values_schema: raw.values_schema,
+            subfeeds: raw.subfeeds,
+            post_build: raw.post_build,
+            feed_checksums: raw.feed_checksums,
+            sign_command: raw.sign_command,
             title: raw.title,
             url: Url::parse(&raw.url).map_err(|_| Error::InvalidCapsuleUrl { url: raw.url })?,
             subtitle: raw.subtitle,
             rights: raw.rights,
+            icon: raw.icon,
+            logo: raw.logo,
+            lang: raw.lang,
+            category_scheme: raw.category_scheme,
+            default_timezone,
+            feed_updated,
+            updated_from_mtime: raw.updated_from_mtime,
             author: raw.author.map(Into::into),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::build::BuildOptions;
+
+    const CONFIG: &str = r#"
+title: "Base"
+url: "gemini://example.com"
+public_dir: "./public/"
+author:
+  name: "Jane Doe"
+  email: "jane@example.com"
+profiles:
+  staging:
+    title: "Staging"
+    public_dir: "./public-staging/"
+  mirror:
+    url: "gemini://mirror.example.com"
+    author:
+      email: "mirror@example.com"
+"#;
+
+    fn mapping(source: &str) -> serde_yaml::Mapping {
+        serde_yaml::from_str(source).unwrap()
+    }
+
+    fn read_config(name: &str, profile: Option<&str>) -> eyre::Result<Config> {
+        let dir = std::env::temp_dir().join(format!("gempost-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("gempost.yaml");
+        fs::write(&path, CONFIG).unwrap();
+        let config = Config::read(&path, profile);
+        fs::remove_dir_all(&dir).unwrap();
+
+        config
+    }
+
+    #[test]
+    fn base_config_without_profile() {
+        let config = read_config("profile-base", None).unwrap();
+
+        assert_eq!(config.title, "Base");
+        assert_eq!(config.public_dir, PathBuf::from("./public/"));
+    }
+
+    #[test]
+    fn profile_overrides_scalars() {
+        let config = read_config("profile-scalar", Some("staging")).unwrap();
+
+        assert_eq!(config.title, "Staging");
+        assert_eq!(config.public_dir, PathBuf::from("./public-staging/"));
+        assert_eq!(config.url.as_str(), "gemini://example.com");
+    }
+
+    #[test]
+    fn profile_replaces_mappings_as_a_whole() {
+        let merged = apply_profile(mapping(CONFIG), "mirror").unwrap();
+
+        assert_eq!(
+            merged["author"],
+            serde_yaml::Value::Mapping(mapping("email: \"mirror@example.com\""))
+        );
+        assert!(!merged.contains_key(PROFILES_KEY));
+
+        // Without the base's `name`, the author is incomplete.
+        assert!(read_config("profile-mapping", Some("mirror")).is_err());
+    }
+
+    #[test]
+    fn unknown_profile_lists_the_profiles() {
+        let err = apply_profile(mapping(CONFIG), "production").unwrap_err();
+
+        assert_eq!(
+            err,
+            "There is no `production` profile. The profiles are: `staging`, `mirror`"
+        );
+    }
+
+    #[test]
+    fn profiles_must_be_a_mapping() {
+        let base = mapping("title: \"Base\"\nprofiles: [\"staging\"]");
+
+        assert_eq!(
+            apply_profile(base, "staging").unwrap_err(),
+            "The `profiles` must be a mapping."
+        );
+    }
+
+    #[test]
+    fn command_line_options_override_profile() {
+        let mut config = read_config("profile-options", Some("staging")).unwrap();
+
+        let options = BuildOptions {
+            output: Some(PathBuf::from("./out/")),
+            capsule_url: Some(Url::parse("gemini://proxy.example.com").unwrap()),
+            ..BuildOptions::default()
+        };
+
+        assert_eq!(options.public_dir(&config), Path::new("./out/"));
+        options.apply_to_config(&mut config);
+
+        assert_eq!(config.url.as_str(), "gemini://proxy.example.com");
+        assert_eq!(config.title, "Staging");
+    }
+}
@@ -1,17 +1,21 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
-use std::path::Path;
-use std::{fs::File, path::PathBuf};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
 
-use chrono::{DateTime, FixedOffset};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveTime};
 use eyre::{bail, eyre, WrapErr};
 use serde::Deserialize;
+use unicode_normalization::UnicodeNormalization;
 use url::Url;
 
+use crate::config::{PostFilesConfig, ValueType, ValuesSchema};
 use crate::error::Error;
+use crate::{gemtext, markdown, xml};
 
-const POST_FILE_EXT: &str = "gmi";
-const METADATA_FILE_EXT: &str = "yaml";
+pub const POST_FILE_EXT: &str = "gmi";
+const MARKDOWN_FILE_EXT: &str = "md";
+pub const METADATA_FILE_EXT: &str = "yaml";
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 struct RawAuthorMetadata {
@@ -20,28 +24,141 @@ struct RawAuthorMetadata {
     uri: Option<String>,
 }
 
+// For backwards compatibility, the `author` can be either a single author or a list of authors.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+enum RawAuthorsMetadata {
+    One(RawAuthorMetadata),
+    Many(Vec<RawAuthorMetadata>),
+}
+
+impl From<RawAuthorsMetadata> for Vec<AuthorMetadata> {
+    fn from(raw: RawAuthorsMetadata) -> Self {
+        match raw {
+            RawAuthorsMetadata::One(author) => vec![author.into()],
+            RawAuthorsMetadata::Many(authors) => authors.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+struct RawEnclosureMetadata {
+    url: String,
+    mime_type: String,
+    length: Option<u64>,
+}
+
 #[derive(Debug, PartialEq, Eq, Deserialize)]
 struct RawEntryMetadata {
     id: String,
     title: String,
-    updated: String,
+    updated: Option<String>,
     summary: Option<String>,
     published: Option<String>,
-    author: Option<RawAuthorMetadata>,
+    expires: Option<String>,
+    author: Option<RawAuthorsMetadata>,
     rights: Option<String>,
     lang: Option<String>,
     categories: Option<Vec<String>>,
     draft: Option<bool>,
+    unlisted: Option<bool>,
+    pinned: Option<bool>,
+    weight: Option<i64>,
+    translation_key: Option<String>,
+    enclosures: Option<Vec<RawEnclosureMetadata>>,
+    redirect: Option<String>,
+    path: Option<String>,
+    slug: Option<String>,
+    aliases: Option<Vec<String>>,
+    inline_links: Option<bool>,
+    heading_shift: Option<usize>,
+    template: Option<PathBuf>,
+    feed_extra: Option<String>,
+    values: Option<BTreeMap<String, serde_yaml::Value>>,
+}
+
+/// Read a file which must be UTF-8, with a readable error saying where it isn't.
+pub fn read_text_file(path: &Path) -> eyre::Result<String> {
+    let bytes = fs::read(path)?;
+
+    String::from_utf8(bytes).map_err(|err| {
+        let valid_bytes = &err.as_bytes()[..err.utf8_error().valid_up_to()];
+
+        eyre!(Error::InvalidUtf8 {
+            path: path.to_owned(),
+            line: valid_bytes.iter().filter(|&&byte| byte == b'\n').count() + 1,
+            offset: valid_bytes.len(),
+        })
+    })
+}
+
+// The line which starts and ends the YAML front matter at the top of a post file.
+const FRONT_MATTER_DELIMITER: &str = "---";
+
+// Split a post file into its YAML front matter and its body, if it starts with front matter. The
+// front matter is everything between a `---` line at the very top of the file and the next one.
+fn split_front_matter(contents: &str) -> Option<(&str, &str)> {
+    let is_delimiter = |line: &str| line.trim_end() == FRONT_MATTER_DELIMITER;
+
+    let (first_line, rest) = contents.split_once('\n')?;
+
+    if !is_delimiter(first_line) {
+        return None;
+    }
+
+    let mut front_matter_len = 0;
+
+    for line in rest.split_inclusive('\n') {
+        if is_delimiter(line) {
+            let front_matter = &rest[..front_matter_len];
+            let body = &rest[front_matter_len + line.len()..];
+
+            return Some((front_matter, body));
+        }
+
+        front_matter_len += line.len();
+    }
+
+    None
 }
 
 // This example comes from the Go standard library.
 const EXAMPLE_RFC3339: &str = "2006-01-02T15:04:05Z07:00";
+const EXAMPLE_DATE: &str = "2006-01-02";
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+// Parse a timestamp in a metadata file, which can be either an RFC 3339 timestamp or just a date.
+// A date is interpreted as midnight at the given offset.
+fn parse_timestamp(
+    value: &str,
+    default_offset: FixedOffset,
+) -> Result<DateTime<FixedOffset>, chrono::ParseError> {
+    match DateTime::parse_from_rfc3339(value) {
+        Ok(datetime) => Ok(datetime),
+        Err(err) => match NaiveDate::parse_from_str(value, DATE_FORMAT) {
+            // With a fixed offset, there is always exactly one matching time.
+            Ok(date) => date
+                .and_time(NaiveTime::MIN)
+                .and_local_timezone(default_offset)
+                .single()
+                .ok_or(err),
+            // If it's not a date either, the problem with the timestamp is more likely to be
+            // useful.
+            Err(_) => Err(err),
+        },
+    }
+}
 
 impl RawEntryMetadata {
     pub fn read(path: &Path) -> eyre::Result<Self> {
-        let metadata_file = File::open(path)?;
+        Self::parse(&read_text_file(path)?, path)
+    }
 
-        let metadata: Self = match serde_yaml::from_reader(metadata_file) {
+    // Parse the metadata from a file, which is either a metadata file or the front matter of a
+    // post file.
+    fn parse(metadata_file: &str, path: &Path) -> eyre::Result<Self> {
+        let metadata: Self = match serde_yaml::from_str(metadata_file) {
             Ok(config) => config,
             Err(err) => bail!(Error::InvalidMetadataFile {
                 path: path.to_owned(),
@@ -70,6 +187,26 @@ impl From<RawAuthorMetadata> for AuthorMetadata {
     }
 }
 
+/// A media file attached to a post, such as the audio for a podcast episode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnclosureMetadata {
+    /// The URL of the file, which may be relative to the capsule URL.
+    pub url: String,
+    pub mime_type: String,
+    /// The size of the file in bytes.
+    pub length: Option<u64>,
+}
+
+impl From<RawEnclosureMetadata> for EnclosureMetadata {
+    fn from(raw: RawEnclosureMetadata) -> Self {
+        Self {
+            url: raw.url,
+            mime_type: raw.mime_type,
+            length: raw.length,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct EntryMetadata {
     pub id: String,
@@ -77,60 +214,292 @@ pub struct EntryMetadata {
     pub updated: DateTime<FixedOffset>,
     pub summary: Option<String>,
     pub published: Option<DateTime<FixedOffset>>,
-    pub author: Option<AuthorMetadata>,
+    /// When this post should stop being published.
+    pub expires: Option<DateTime<FixedOffset>>,
+    pub authors: Vec<AuthorMetadata>,
     pub rights: Option<String>,
     pub lang: Option<String>,
     pub categories: Vec<String>,
     pub draft: bool,
+    /// Whether to leave this post out of the feeds and the index while still generating its page.
+    pub unlisted: bool,
+    /// Whether to list this post before the others, no matter when it was published.
+    pub pinned: bool,
+    /// Where to list this post among the unlisted posts. Lower weights come first.
+    pub weight: Option<i64>,
+    /// Posts with the same key are translations of each other.
+    pub translation_key: Option<String>,
+    pub enclosures: Vec<EnclosureMetadata>,
+    /// The URL this post has moved to, which may be relative to the capsule URL.
+    pub redirect: Option<String>,
+    /// The URL path of the post, which overrides the `post_path` in the config.
+    pub path: Option<String>,
+    /// The slug of the post, which overrides the one from its filename.
+    pub slug: Option<String>,
+    /// Previous URL paths of the post, which redirect to it.
+    pub aliases: Vec<String>,
+    /// Whether to expand inline links, which overrides the `inline_links` in the config.
+    pub inline_links: Option<bool>,
+    /// How many levels to shift the headings in this post down by.
+    pub heading_shift: usize,
+    /// The template to render this post with, which overrides the `post_template_file` in the
+    /// config.
+    pub template: Option<PathBuf>,
+    /// Raw XML to include in this post's entry in the Atom feed, like extension elements.
+    pub feed_extra: Option<String>,
+    /// Arbitrary custom values for use in templates.
+    pub values: BTreeMap<String, serde_yaml::Value>,
+}
+
+// Check that a post path in a metadata file stays within the capsule. The `field` is the name of
+// the metadata field the path came from.
+fn check_post_path(field: &str, post_path: &str) -> Result<(), String> {
+    if Url::parse(post_path).is_ok() {
+        return Err(format!(
+            "The post `{field}` must be a path within your capsule, not a full URL: `{post_path}`"
+        ));
+    }
+
+    if post_path.split('/').any(|segment| segment == "..") {
+        return Err(format!(
+            "The post `{field}` cannot contain `..`: `{post_path}`"
+        ));
+    }
+
+    if post_path.ends_with('/') || post_path.split('/').all(str::is_empty) {
+        return Err(format!(
+            "The post `{field}` must end with a file name: `{post_path}`"
+        ));
+    }
+
+    Ok(())
+}
+
+// Check that a slug in a metadata file can be used in a URL as it is.
+fn check_slug(slug: &str) -> Result<(), String> {
+    if slug.is_empty() || slug == "." || slug == ".." {
+        return Err(format!("The post `slug` is not a valid slug: `{slug}`"));
+    }
+
+    if let Some(c) = slug
+        .chars()
+        .find(|&c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~')))
+    {
+        return Err(format!(
+            "The post `slug` can only contain letters, numbers, `-`, `.`, `_`, and `~`, but it contains `{c}`: `{slug}`"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Options for reading post metadata files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetadataOptions {
+    /// The offset that dates without a time are interpreted as midnight at.
+    pub default_offset: FixedOffset,
+
+    /// Whether to use the modification time of the post when the metadata file has no `updated`
+    /// time.
+    pub updated_from_mtime: bool,
+}
+
+// The modification time of a file, at the given offset.
+fn modified_time(path: &Path, offset: FixedOffset) -> eyre::Result<DateTime<FixedOffset>> {
+    let modified = fs::metadata(path)?.modified()?;
+    let since_epoch = modified.duration_since(UNIX_EPOCH)?;
+
+    let modified = DateTime::from_timestamp(
+        i64::try_from(since_epoch.as_secs())?,
+        since_epoch.subsec_nanos(),
+    )
+    .ok_or(eyre!("The modification time is out of range."))?;
+
+    Ok(modified.with_timezone(&offset))
 }
 
 impl EntryMetadata {
-    pub fn read(path: &Path) -> eyre::Result<Self> {
+    /// Read a metadata file. Dates without a time are interpreted as midnight at the
+    /// `default_offset` in the options.
+    pub fn read(path: &Path, options: MetadataOptions) -> eyre::Result<Self> {
         let raw = RawEntryMetadata::read(path).wrap_err(format!(
             "failed reading metadata file: {}",
             path.to_string_lossy()
         ))?;
 
-        Ok(Self {
-            id: raw.id,
-            title: raw.title,
-            updated: DateTime::parse_from_rfc3339(&raw.updated).map_err(|_| {
+        Self::from_raw(raw, path, &post_source_path(path), options)
+    }
+
+    /// Parse the front matter at the top of a post file, the same way as a metadata file.
+    pub fn from_front_matter(
+        front_matter: &str,
+        post_path: &Path,
+        options: MetadataOptions,
+    ) -> eyre::Result<Self> {
+        let raw = RawEntryMetadata::parse(front_matter, post_path).wrap_err(format!(
+            "failed reading front matter: {}",
+            post_path.to_string_lossy()
+        ))?;
+
+        Self::from_raw(raw, post_path, post_path, options)
+    }
+
+    // The `path` is the file the metadata came from, and the `post_path` is the post it's for,
+    // which are the same file for front matter.
+    fn from_raw(
+        raw: RawEntryMetadata,
+        path: &Path,
+        post_path: &Path,
+        options: MetadataOptions,
+    ) -> eyre::Result<Self> {
+        let default_offset = options.default_offset;
+
+        if let Some(post_path) = &raw.path {
+            check_post_path("path", post_path).map_err(|reason| Error::InvalidMetadataFile {
+                path: path.to_owned(),
+                reason,
+            })?;
+        }
+
+        if let Some(slug) = &raw.slug {
+            check_slug(slug).map_err(|reason| Error::InvalidMetadataFile {
+                path: path.to_owned(),
+                reason,
+            })?;
+        }
+
+        for alias in raw.aliases.iter().flatten() {
+            check_post_path("aliases", alias).map_err(|reason| Error::InvalidMetadataFile {
+                path: path.to_owned(),
+                reason,
+            })?;
+        }
+
+        if let Some(template) = &raw.template {
+            if !template.is_file() {
+                bail!(Error::InvalidMetadataFile {
+                    path: path.to_owned(),
+                    reason: format!(
+                        "The post `template` does not exist: {}",
+                        template.to_string_lossy()
+                    ),
+                });
+            }
+        }
+
+        if let Some(feed_extra) = &raw.feed_extra {
+            xml::check_fragment(feed_extra).map_err(|reason| Error::InvalidMetadataFile {
+                path: path.to_owned(),
+                reason: format!("The post `feed_extra` is not well-formed XML. {reason}"),
+            })?;
+        }
+
+        let updated = match &raw.updated {
+            Some(updated) => parse_timestamp(updated, default_offset).map_err(|_| {
                 Error::InvalidMetadataFile {
                     path: path.to_owned(),
                     reason: format!(
-                        "The post `updated` time must be in RFC 3339 format (e.g. {EXAMPLE_RFC3339})."
+                        "The post `updated` time must be in RFC 3339 format (e.g. {EXAMPLE_RFC3339}) or a date (e.g. {EXAMPLE_DATE})."
                     ),
                 }
             })?,
+            // An explicit `updated` time always wins, but if there isn't one, we can fall back to
+            // when the post was last modified.
+            None if options.updated_from_mtime => {
+                modified_time(post_path, default_offset).map_err(|err| {
+                    Error::InvalidMetadataFile {
+                        path: path.to_owned(),
+                        reason: format!(
+                            "The post has no `updated` time, and the modification time of `{}` could not be read.\n\n{err}",
+                            post_path.to_string_lossy()
+                        ),
+                    }
+                })?
+            }
+            None => bail!(Error::InvalidMetadataFile {
+                path: path.to_owned(),
+                reason: String::from("The post must have an `updated` time."),
+            }),
+        };
+
+        Ok(Self {
+            id: raw.id,
+            title: raw.title,
+            updated,
             summary: raw.summary,
             published: raw
                 .published
                 .as_ref()
-                .map(|published| DateTime::parse_from_rfc3339(published).map_err(|_| {
+                .map(|published| parse_timestamp(published, default_offset).map_err(|_| {
                     Error::InvalidMetadataFile {
                         path: path.to_owned(),
                         reason: format!(
-                            "The post `published` time must be in RFC 3339 format (e.g. {EXAMPLE_RFC3339})."
+                            "The post `published` time must be in RFC 3339 format (e.g. {EXAMPLE_RFC3339}) or a date (e.g. {EXAMPLE_DATE})."
                         ),
                     }
                 }))
                 .transpose()?,
-            author: raw.author.map(Into::into),
+            expires: raw
+                .expires
+                .as_ref()
+                .map(|expires| parse_timestamp(expires, default_offset).map_err(|_| {
+                    Error::InvalidMetadataFile {
+                        path: path.to_owned(),
+                        reason: format!(
+                            "The post `expires` time must be in RFC 3339 format (e.g. {EXAMPLE_RFC3339}) or a date (e.g. {EXAMPLE_DATE})."
+                        ),
+                    }
+                }))
+                .transpose()?,
+            authors: raw.author.map(Into::into).unwrap_or_default(),
             rights: raw.rights,
             lang: raw.lang,
             categories: raw.categories.unwrap_or_default(),
             // If the `draft` property is missing, we assume it's not a draft.
             draft: raw.draft.unwrap_or(false),
+            unlisted: raw.unlisted.unwrap_or(false),
+            pinned: raw.pinned.unwrap_or(false),
+            weight: raw.weight,
+            translation_key: raw.translation_key,
+            enclosures: raw
+                .enclosures
+                .unwrap_or_default()
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            redirect: raw.redirect,
+            path: raw.path,
+            slug: raw.slug,
+            aliases: raw.aliases.unwrap_or_default(),
+            inline_links: raw.inline_links,
+            heading_shift: raw.heading_shift.unwrap_or(0),
+            template: raw.template,
+            feed_extra: raw.feed_extra,
+            values: raw.values.unwrap_or_default(),
         })
     }
 }
 
+/// Another language version of a post.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Translation {
+    pub lang: Option<String>,
+    pub url: Url,
+    pub title: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Entry {
     pub metadata: EntryMetadata,
     pub body: String,
     pub url: Url,
     pub path: PathBuf,
+    pub source_path: PathBuf,
+    /// When the post file was last modified, if it could be read.
+    pub file_modified: Option<DateTime<FixedOffset>>,
+    pub metadata_path: PathBuf,
+    /// The other posts with the same `translation_key`, which are found when the feed is assembled.
+    pub translations: Vec<Translation>,
 }
 
 pub struct PostLocation {
@@ -152,29 +521,72 @@ fn change_file_ext(path: &Path, new_ext: &str) -> Option<PathBuf> {
     Some(path.parent()?.join(new_filename))
 }
 
-struct PostPathPair {
-    gemtext: PathBuf,
-    metadata: PathBuf,
+// The post file which goes with a metadata file. A post can be written in gemtext or Markdown, but
+// if there are both, the gemtext file wins.
+fn post_source_path(metadata_path: &Path) -> PathBuf {
+    let markdown_path = metadata_path.with_extension(MARKDOWN_FILE_EXT);
+
+    if markdown_path.exists() && !metadata_path.with_extension(POST_FILE_EXT).exists() {
+        markdown_path
+    } else {
+        metadata_path.with_extension(POST_FILE_EXT)
+    }
+}
+
+// The slug of a post which doesn't set one, which comes from its filename. Some filesystems, like
+// the one on macOS, store filenames in decomposed form. We normalize them so the post URLs are the
+// same no matter where the capsule is built.
+fn slug_from_path(post_path: &Path) -> eyre::Result<String> {
+    Ok(post_path
+        .file_stem()
+        .ok_or(eyre!(
+            "This filename does not have a file stem. This is a bug.\n{}",
+            post_path.to_string_lossy()
+        ))?
+        .to_string_lossy()
+        .nfc()
+        .collect())
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == MARKDOWN_FILE_EXT)
+}
+
+/// The files a post is read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostPathPair {
+    pub post: PathBuf,
+    /// This is `None` when the metadata is front matter in the post file.
+    pub metadata: Option<PathBuf>,
+    /// The contents of the post file, which we read once to look for front matter.
+    pub contents: String,
 }
 
 // Remove paths from each set that do not have an accompanying path in the other set. Emit warnings
-// when this happens.
+// when this happens, or fail if `strict` is set.
 fn check_mismatched_post_files(
-    post_paths: HashSet<PathBuf>,
-    metadata_paths: &HashSet<PathBuf>,
+    post_paths: BTreeSet<PathBuf>,
+    metadata_paths: &BTreeSet<PathBuf>,
+    strict: bool,
     warn_handler: impl Fn(&str),
 ) -> eyre::Result<Vec<PostPathPair>> {
-    // Warn about metadata files that don't have an accompanying gemtext file.
+    let mut mismatches = Vec::new();
+
+    // Find metadata files that don't have an accompanying gemtext file.
     for metadata_path in metadata_paths.iter() {
-        let maybe_post_path = match change_file_ext(metadata_path, POST_FILE_EXT) {
-            Some(path) => path,
-            None => bail!("This file has no filename, even though we've already checked for one. This is a bug."),
+        let (maybe_post_path, maybe_markdown_path) = match (
+            change_file_ext(metadata_path, POST_FILE_EXT),
+            change_file_ext(metadata_path, MARKDOWN_FILE_EXT),
+        ) {
+            (Some(post_path), Some(markdown_path)) => (post_path, markdown_path),
+            _ => bail!("This file has no filename, even though we've already checked for one. This is a bug."),
         };
 
-        if !post_paths.contains(&maybe_post_path) {
-            warn_handler(&format!(
-                "This YAML metadata file does not have an accompanying gemtext file: {}",
-                metadata_path.to_string_lossy()
+        if !post_paths.contains(&maybe_post_path) && !post_paths.contains(&maybe_markdown_path) {
+            mismatches.push(format!(
+                "This YAML metadata file does not have an accompanying gemtext file: {}\nExpected a gemtext file at: {}",
+                metadata_path.to_string_lossy(),
+                maybe_post_path.to_string_lossy()
             ));
         }
     }
@@ -182,99 +594,388 @@ fn check_mismatched_post_files(
     let mut pairs = Vec::new();
 
     // Filter out gemtext files that don't have an accompanying metadata file.
-    for post_path in post_paths.into_iter() {
-        let maybe_metadata_path = match change_file_ext(&post_path, METADATA_FILE_EXT) {
+    for post_path in post_paths.iter() {
+        // A Markdown file which has a gemtext file alongside it isn't a separate post.
+        if is_markdown(post_path) && post_paths.contains(&post_path.with_extension(POST_FILE_EXT)) {
+            mismatches.push(format!(
+                "This Markdown file has a gemtext file with the same name, so it will be ignored: {}",
+                post_path.to_string_lossy()
+            ));
+            continue;
+        }
+
+        let maybe_metadata_path = match change_file_ext(post_path, METADATA_FILE_EXT) {
             Some(path) => path,
             None => bail!("This file has no filename, even though we've already checked for one. This is a bug."),
         };
 
+        let contents = read_text_file(post_path).wrap_err("failed reading post body")?;
+
+        // A post with both a metadata file and front matter is an error, which we report when we
+        // read its metadata.
         if metadata_paths.contains(&maybe_metadata_path) {
             pairs.push(PostPathPair {
-                gemtext: post_path,
-                metadata: maybe_metadata_path,
+                post: post_path.clone(),
+                metadata: Some(maybe_metadata_path),
+                contents,
+            });
+        } else if split_front_matter(&contents).is_some() {
+            pairs.push(PostPathPair {
+                post: post_path.clone(),
+                metadata: None,
+                contents,
             });
         } else {
-            warn_handler(&format!(
-                "This gemtext file does not have an accompanying YAML metadata file: {}",
-                post_path.to_string_lossy()
+            mismatches.push(format!(
+                "This {} file does not have an accompanying YAML metadata file or front matter: {}\nExpected a metadata file at: {}",
+                if is_markdown(post_path) { "Markdown" } else { "gemtext" },
+                post_path.to_string_lossy(),
+                maybe_metadata_path.to_string_lossy()
             ));
         }
     }
 
+    // The paths come from sets, so sort them to report them in a consistent order.
+    mismatches.sort();
+
+    if strict && !mismatches.is_empty() {
+        bail!(Error::MismatchedPostFiles {
+            reason: mismatches.join("\n\n"),
+        });
+    }
+
+    for mismatch in &mismatches {
+        warn_handler(mismatch);
+    }
+
     Ok(pairs)
 }
 
+/// Check that every entry has a unique ID, and warn about IDs which are not valid URIs.
+///
+/// Atom requires that entry IDs be permanent, globally unique URIs.
+pub fn check_entry_ids(entries: &[Entry], warn_handler: impl Fn(&str)) -> eyre::Result<()> {
+    let mut paths_by_id = BTreeMap::<&str, Vec<&Path>>::new();
+
+    for entry in entries {
+        paths_by_id
+            .entry(&entry.metadata.id)
+            .or_default()
+            .push(&entry.source_path);
+
+        if Url::parse(&entry.metadata.id).is_err() {
+            warn_handler(&format!(
+                "This post ID is not a valid URI, which Atom requires: {} ({})",
+                entry.metadata.id,
+                entry.source_path.to_string_lossy()
+            ));
+        }
+    }
+
+    let duplicates = paths_by_id
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(id, mut paths)| {
+            paths.sort();
+
+            let path_list = paths
+                .iter()
+                .map(|path| format!("- {}", path.to_string_lossy()))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            format!("`{id}` is used by:\n{path_list}")
+        })
+        .collect::<Vec<_>>();
+
+    if !duplicates.is_empty() {
+        bail!(Error::DuplicatePostIds {
+            reason: duplicates.join("\n\n"),
+        });
+    }
+
+    Ok(())
+}
+
+/// Check that no entry was published after it was updated.
+///
+/// This is an error unless `lenient` is set, in which case we only warn about it.
+pub fn check_entry_dates(
+    entries: &[Entry],
+    lenient: bool,
+    warn_handler: impl Fn(&str),
+) -> eyre::Result<()> {
+    for entry in entries {
+        let metadata = &entry.metadata;
+
+        let published = match metadata.published {
+            Some(published) => published,
+            None => continue,
+        };
+
+        // This compares the instants, so it works even if the timestamps have different offsets.
+        if published <= metadata.updated {
+            continue;
+        }
+
+        let err = Error::PublishedAfterUpdated {
+            path: entry.metadata_path.clone(),
+            published: published.to_rfc3339(),
+            updated: metadata.updated.to_rfc3339(),
+        };
+
+        if lenient {
+            warn_handler(&err.to_string());
+        } else {
+            bail!(err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that every preformatted block in each entry is closed, and optionally warn about blocks
+/// which have no alt text.
+pub fn check_entry_preformatting(
+    entries: &[Entry],
+    warn_missing_alt: bool,
+    warn_handler: impl Fn(&str),
+) -> eyre::Result<()> {
+    for entry in entries {
+        let lines = gemtext::parse(&entry.body);
+
+        for block in gemtext::preformatted_blocks(&lines) {
+            if !block.closed {
+                bail!(Error::UnclosedPreformattedBlock {
+                    path: entry.source_path.clone(),
+                    line: block.line,
+                });
+            }
+
+            if warn_missing_alt && block.alt.is_none() {
+                warn_handler(&format!(
+                    "This post has a preformatted block on line {} without alt text, which screen readers need to describe it: {}",
+                    block.line,
+                    entry.source_path.to_string_lossy()
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// A name for the type of a YAML value, for error messages.
+fn value_type_name(value: &serde_yaml::Value) -> &'static str {
+    match value {
+        serde_yaml::Value::Null => "null",
+        serde_yaml::Value::Bool(_) => "bool",
+        serde_yaml::Value::Number(_) => "number",
+        serde_yaml::Value::String(_) => "string",
+        serde_yaml::Value::Sequence(_) => "list",
+        serde_yaml::Value::Mapping(_) => "mapping",
+        serde_yaml::Value::Tagged(_) => "tagged value",
+    }
+}
+
+fn is_value_type(value: &serde_yaml::Value, value_type: ValueType) -> bool {
+    matches!(
+        (value, value_type),
+        (serde_yaml::Value::String(_), ValueType::String)
+            | (serde_yaml::Value::Number(_), ValueType::Number)
+            | (serde_yaml::Value::Bool(_), ValueType::Bool)
+            | (serde_yaml::Value::Sequence(_), ValueType::List)
+    )
+}
+
+/// Check that the custom `values` of every entry match the schema in the config.
+pub fn check_entry_values(entries: &[Entry], schema: &ValuesSchema) -> eyre::Result<()> {
+    for entry in entries {
+        let values = &entry.metadata.values;
+
+        let invalid = |reason: String| Error::InvalidMetadataFile {
+            path: entry.metadata_path.clone(),
+            reason,
+        };
+
+        for (key, &value_type) in &schema.required {
+            match values.get(key) {
+                Some(value) if is_value_type(value, value_type) => {}
+                Some(value) => bail!(invalid(format!(
+                    "The `{key}` in `values` must be a {value_type}, but it is a {}.",
+                    value_type_name(value)
+                ))),
+                None => bail!(invalid(format!(
+                    "The `values` must include `{key}`, which must be a {value_type}."
+                ))),
+            }
+        }
+
+        if schema.strict {
+            if let Some(key) = values
+                .keys()
+                .find(|key| !schema.required.contains_key(*key))
+            {
+                bail!(invalid(format!(
+                    "The `{key}` in `values` is not in the `values_schema` in the config."
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Read every post in the posts directory, returning an error for each one which is invalid.
+///
+/// Posts are found the same way as `Entry::from_posts`, but this doesn't stop at the first invalid
+/// post, whether its metadata is in a YAML file or front matter.
+pub fn check_posts(
+    posts_dir: &Path,
+    post_files: &PostFilesConfig,
+    locator: impl Fn(PostLocationParams) -> eyre::Result<PostLocation>,
+    options: MetadataOptions,
+    strict: bool,
+    warn_handler: impl Fn(&str),
+) -> eyre::Result<Vec<eyre::Report>> {
+    let path_pairs = Entry::find_posts(posts_dir, post_files, strict, warn_handler)?;
+
+    Ok(path_pairs
+        .into_iter()
+        .filter_map(|pair| {
+            Entry::from_post(
+                &pair.post,
+                pair.contents,
+                pair.metadata.as_deref(),
+                &locator,
+                options,
+            )
+            .err()
+        })
+        .collect())
+}
+
 impl Entry {
+    /// Create an entry from the contents of a post file and the path of its metadata file.
+    ///
+    /// The post doesn't need to be in the posts directory. Its path is where the contents came
+    /// from, and it's used for the slug unless the metadata sets one. Without a metadata file, the
+    /// metadata comes from the front matter at the top of the post.
+    pub fn from_post(
+        post_path: &Path,
+        post_contents: String,
+        metadata_path: Option<&Path>,
+        locator: impl Fn(PostLocationParams) -> eyre::Result<PostLocation>,
+        options: MetadataOptions,
+    ) -> eyre::Result<Self> {
+        let (post_metadata, post_body) = match metadata_path {
+            // Otherwise, the front matter would end up in the body of the post.
+            Some(metadata_path) if split_front_matter(&post_contents).is_some() => {
+                bail!(Error::ConflictingFrontMatter {
+                    path: post_path.to_owned(),
+                    metadata_path: metadata_path.to_owned(),
+                })
+            }
+            Some(metadata_path) => (EntryMetadata::read(metadata_path, options)?, post_contents),
+            None => match split_front_matter(&post_contents) {
+                Some((front_matter, body)) => (
+                    EntryMetadata::from_front_matter(front_matter, post_path, options)?,
+                    body.to_owned(),
+                ),
+                None => bail!(Error::MissingFrontMatter {
+                    path: post_path.to_owned(),
+                }),
+            },
+        };
+
+        // Markdown posts are converted up front, so everything after this only sees gemtext.
+        let post_body = if is_markdown(post_path) {
+            markdown::to_gemtext(&post_body)
+        } else {
+            post_body
+        };
+
+        let post_slug = match &post_metadata.slug {
+            Some(slug) => slug.clone(),
+            None => slug_from_path(post_path)?,
+        };
+
+        let post_location = locator(PostLocationParams {
+            metadata: &post_metadata,
+            slug: &post_slug,
+        })?;
+
+        Ok(Entry {
+            metadata: post_metadata,
+            body: post_body,
+            url: post_location.url,
+            path: post_location.path,
+            source_path: post_path.to_owned(),
+            // A post from stdin has no file, so this is allowed to fail.
+            file_modified: modified_time(post_path, options.default_offset).ok(),
+            metadata_path: metadata_path.unwrap_or(post_path).to_owned(),
+            translations: Vec::new(),
+        })
+    }
+
     fn from_post_paths(
-        path_pairs: &Vec<PostPathPair>,
+        path_pairs: Vec<PostPathPair>,
         locator: impl Fn(PostLocationParams) -> eyre::Result<PostLocation>,
+        options: MetadataOptions,
     ) -> eyre::Result<Vec<Self>> {
         let mut entries = Vec::new();
 
         // By this point, we've already removed post paths from the set that do not have an
         // accompanying metadata file.
         for PostPathPair {
-            gemtext: gemtext_path,
+            post: post_path,
             metadata: metadata_path,
+            contents,
         } in path_pairs
         {
-            let post_body = String::from_utf8(
-                fs::read(gemtext_path).wrap_err("failed reading gemtext post body")?,
-            )
-            .wrap_err("gemtext post body is not valid UTF-8")?;
-
-            let post_metadata = EntryMetadata::read(metadata_path)?;
-
-            // We do not publish draft posts.
-            if post_metadata.draft {
-                continue;
-            }
-
-            let post_slug = gemtext_path
-                .file_stem()
-                .ok_or(eyre!(
-                    "This filename does not have a file stem. This is a bug.\n{}",
-                    gemtext_path.to_string_lossy()
-                ))?
-                .to_string_lossy();
-
-            let post_location = locator(PostLocationParams {
-                metadata: &post_metadata,
-                slug: &post_slug,
-            })?;
-
-            entries.push(Entry {
-                metadata: post_metadata,
-                body: post_body,
-                url: post_location.url,
-                path: post_location.path,
-            });
+            entries.push(Self::from_post(
+                &post_path,
+                contents,
+                metadata_path.as_deref(),
+                &locator,
+                options,
+            )?);
         }
 
         Ok(entries)
     }
 
-    pub fn from_posts(
+    /// Find the files each post in the posts directory is read from.
+    ///
+    /// Files which aren't selected by the config are skipped without a warning.
+    pub fn find_posts(
         posts_dir: &Path,
-        locator: impl Fn(PostLocationParams) -> eyre::Result<PostLocation>,
+        post_files: &PostFilesConfig,
+        strict: bool,
         warn_handler: impl Fn(&str),
-    ) -> eyre::Result<Vec<Self>> {
+    ) -> eyre::Result<Vec<PostPathPair>> {
         let file_entries = fs::read_dir(posts_dir).wrap_err("failed reading posts directory")?;
 
-        let mut post_paths = HashSet::new();
-        let mut metadata_paths = HashSet::new();
+        // These are sorted so posts are always read in the same order, no matter what order the
+        // filesystem lists them in.
+        let mut post_paths = BTreeSet::new();
+        let mut metadata_paths = BTreeSet::new();
 
         let warn_unexpected_file_ext = |path: &Path| {
             warn_handler(&format!(
-                "This is not a .gmi or .yaml file: {}",
+                "This is not a .gmi, .md, or .yaml file: {}",
                 path.as_os_str().to_string_lossy()
             ));
         };
 
         for entry_result in file_entries {
-            let entry_path = entry_result
-                .wrap_err("failed reading posts directory")?
-                .path();
+            let dir_entry = entry_result.wrap_err("failed reading posts directory")?;
+
+            if !post_files.is_selected(Path::new(&dir_entry.file_name())) {
+                continue;
+            }
+
+            let entry_path = dir_entry.path();
 
             let path_ext = match entry_path.extension() {
                 Some(extension) => extension,
@@ -285,7 +986,7 @@ impl Entry {
             };
 
             match path_ext.to_string_lossy().as_ref() {
-                POST_FILE_EXT => post_paths.insert(entry_path),
+                POST_FILE_EXT | MARKDOWN_FILE_EXT => post_paths.insert(entry_path),
                 METADATA_FILE_EXT => metadata_paths.insert(entry_path),
                 _ => {
                     warn_unexpected_file_ext(&entry_path);
@@ -294,9 +995,94 @@ impl Entry {
             };
         }
 
-        let path_pairs = check_mismatched_post_files(post_paths, &metadata_paths, warn_handler)
-            .wrap_err("failed checking for mismatched post files")?;
+        check_mismatched_post_files(post_paths, &metadata_paths, strict, warn_handler)
+            .wrap_err("failed checking for mismatched post files")
+    }
+
+    pub fn from_posts(
+        posts_dir: &Path,
+        post_files: &PostFilesConfig,
+        locator: impl Fn(PostLocationParams) -> eyre::Result<PostLocation>,
+        options: MetadataOptions,
+        strict: bool,
+        warn_handler: impl Fn(&str),
+    ) -> eyre::Result<Vec<Self>> {
+        let path_pairs = Self::find_posts(posts_dir, post_files, strict, warn_handler)?;
+
+        Self::from_post_paths(path_pairs, locator, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugs_from_decomposed_filenames_are_nfc() {
+        // "café" with the accent as a separate combining character, like macOS stores it.
+        let decomposed = Path::new("posts/cafe\u{301}.gmi");
+
+        assert_eq!(slug_from_path(decomposed).unwrap(), "caf\u{e9}");
+    }
+
+    #[test]
+    fn slugs_from_composed_filenames_are_unchanged() {
+        let composed = Path::new("posts/caf\u{e9}.gmi");
+
+        assert_eq!(slug_from_path(composed).unwrap(), "caf\u{e9}");
+    }
+
+    #[test]
+    fn check_posts_reports_every_invalid_post() {
+        let posts_dir =
+            std::env::temp_dir().join(format!("gempost-check-posts-{}", std::process::id()));
+        fs::create_dir_all(&posts_dir).unwrap();
+
+        let files = [
+            ("valid.gmi", "Hello"),
+            ("valid.yaml", "id: valid\ntitle: Valid\nupdated: 2024-01-01"),
+            ("no-updated.gmi", "Hello"),
+            ("no-updated.yaml", "id: no-updated\ntitle: No updated"),
+            (
+                "front-matter.gmi",
+                "---\nid: front-matter\ntitle: Front matter\n---\nHello",
+            ),
+            (
+                "markdown.md",
+                "---\nid: markdown\nupdated: yesterday\n---\nHello",
+            ),
+        ];
+
+        for (name, contents) in files {
+            fs::write(posts_dir.join(name), contents).unwrap();
+        }
+
+        let post_files = PostFilesConfig {
+            include: None,
+            ignore: globset::GlobSet::empty(),
+        };
+        let locator = |params: PostLocationParams| -> eyre::Result<PostLocation> {
+            Ok(PostLocation {
+                url: Url::parse(&format!("gemini://example.com/{}.gmi", params.slug))?,
+                path: PathBuf::from(format!("{}.gmi", params.slug)),
+            })
+        };
+        let options = MetadataOptions {
+            default_offset: FixedOffset::east_opt(0).unwrap(),
+            updated_from_mtime: false,
+        };
+
+        let errors = check_posts(&posts_dir, &post_files, locator, options, true, |_| {}).unwrap();
+        let messages = errors
+            .iter()
+            .map(|err| format!("{err:?}"))
+            .collect::<Vec<_>>();
+
+        fs::remove_dir_all(&posts_dir).unwrap();
 
-        Self::from_post_paths(&path_pairs, locator)
+        assert_eq!(messages.len(), 3, "{messages:#?}");
+        assert!(messages[0].contains("front-matter.gmi"));
+        assert!(messages[1].contains("markdown.md"));
+        assert!(messages[2].contains("no-updated.yaml"));
     }
 }
@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 
+use chrono::NaiveDate;
 use thiserror::Error;
 
 /// A error type for user-facing errors.
@@ -11,27 +12,137 @@ pub enum Error {
     #[error("There is no config file at `{path}`.")]
     NonexistentConfigFile { path: PathBuf },
 
+    #[error("There is no post file at `{path}`.")]
+    NonexistentPostFile { path: PathBuf },
+
+    #[error("There is no metadata file at `{path}`.")]
+    NonexistentMetadataFile { path: PathBuf },
+
     #[error("There is a problem with the config file at `{path}`.\n\n{reason}")]
     InvalidConfigFile { path: PathBuf, reason: String },
 
     #[error("There is a problem with the post metadata file at `{path}`.\n\n{reason}")]
     InvalidMetadataFile { path: PathBuf, reason: String },
 
-    #[error("You cannot initialize this directory as a gempost project because this file already exists: {path}")]
+    #[error(
+        "The post at `{path}` has no metadata file, and it doesn't start with `---` front matter."
+    )]
+    MissingFrontMatter { path: PathBuf },
+
+    #[error("The post at `{path}` has both a metadata file and `---` front matter. Only use one of them.\n\nMetadata file: `{metadata_path}`")]
+    ConflictingFrontMatter {
+        path: PathBuf,
+        metadata_path: PathBuf,
+    },
+
+    #[error("The file at `{path}` is not valid UTF-8. The first invalid byte is on line {line}, at byte {offset} of the file.\n\nIf it was saved in another encoding, like Latin-1, you need to convert it to UTF-8.")]
+    InvalidUtf8 {
+        path: PathBuf,
+        line: usize,
+        offset: usize,
+    },
+
+    #[error("There is a problem with an `@include` in the post at `{path}`.\n\n{reason}")]
+    InvalidPostInclude { path: PathBuf, reason: String },
+
+    #[error("The post at `{path}` has a preformatted block starting on line {line} which is never closed. Add a line starting with ``` after the end of the block.")]
+    UnclosedPreformattedBlock { path: PathBuf, line: usize },
+
+    #[error("The post metadata file at `{path}` has a `published` time which is after its `updated` time.\n\nPublished: {published}\nUpdated: {updated}")]
+    PublishedAfterUpdated {
+        path: PathBuf,
+        published: String,
+        updated: String,
+    },
+
+    #[error("Every post needs both a gemtext file and a YAML metadata file, but some are missing one.\n\n{reason}")]
+    MismatchedPostFiles { reason: String },
+
+    #[error("Every post must have a unique ID, but some posts share the same ID.\n\n{reason}")]
+    DuplicatePostIds { reason: String },
+
+    #[error("You cannot initialize this directory as a gempost project because this file already exists: {path}\n\nPass `--force` to overwrite it.")]
     ExampleFileAlreadyExists { path: PathBuf },
 
     #[error("There is already a post with this slug: {slug}")]
     PostAlreadyExists { slug: String },
 
+    #[error("The post at `{path}` has a `redirect`, so it has no page to render.")]
+    RedirectedPost { path: PathBuf },
+
     #[error("There was an issue generating the index page.\n\n{reason}")]
     InvalidIndexPageTemplate { reason: String },
 
     #[error("There was an issue generating a post page.\n\n{reason}")]
     InvalidPostPageTemplate { path: PathBuf, reason: String },
 
+    #[error("There was an issue generating the Atom feed.\n\n{reason}")]
+    InvalidFeedTemplate { reason: String },
+
+    #[error("There was an issue generating a category page.\n\n{reason}")]
+    InvalidCategoryPageTemplate { reason: String },
+
+    #[error("There was an issue generating the category index page.\n\n{reason}")]
+    InvalidCategoriesPageTemplate { reason: String },
+
+    #[error("There was an issue generating the archive page.\n\n{reason}")]
+    InvalidArchivePageTemplate { reason: String },
+
+    #[error("There was an issue generating the robots.txt file.\n\n{reason}")]
+    InvalidRobotsTemplate { reason: String },
+
+    #[error("There was an issue generating the security.txt file.\n\n{reason}")]
+    InvalidSecurityTemplate { reason: String },
+
+    #[error("There was an issue generating a redirect page.\n\n{reason}")]
+    InvalidRedirectPageTemplate { reason: String },
+
     #[error("The post path template in your gempost.yaml is invalid.\n\nTemplate: `{template}`\n\n{reason}")]
     InvalidPostPath { template: String, reason: String },
 
+    #[error("The category path template in your gempost.yaml is invalid.\n\nTemplate: `{template}`\n\n{reason}")]
+    InvalidCategoryPath { template: String, reason: String },
+
+    #[error("The index page path template in your gempost.yaml is invalid.\n\nTemplate: `{template}`\n\n{reason}")]
+    InvalidIndexPagePath { template: String, reason: String },
+
+    #[error("The `{field}` URL in your gempost.yaml is invalid.\n\nURL: `{url}`\n\n{reason}")]
+    InvalidConfigUrl {
+        field: String,
+        url: String,
+        reason: String,
+    },
+
+    #[error("The build had warnings, which are errors with `--strict`.\n\n{reason}")]
+    StrictWarnings { reason: String },
+
+    #[error("Some links in your capsule point to pages which don't exist.\n\n{reason}")]
+    BrokenLinks { reason: String },
+
+    #[error(
+        "The `post_build` command in your gempost.yaml failed.\n\nCommand: `{command}`\n\n{reason}"
+    )]
+    PostBuildFailed { command: String, reason: String },
+
+    #[error("The `sign_command` in your gempost.yaml failed to sign `{path}`.\n\nCommand: `{command}`\n\n{reason}")]
+    SignCommandFailed {
+        path: PathBuf,
+        command: String,
+        reason: String,
+    },
+
+    #[error("The template at `{path}` is invalid.\n\n{reason}")]
+    InvalidTemplate { path: PathBuf, reason: String },
+
+    #[error("There was an issue loading the templates in the includes directory.\n\n{reason}")]
+    InvalidIncludeTemplate { reason: String },
+
+    #[error("Found {count} problem(s) with your capsule.")]
+    ValidationFailed { count: usize },
+
     #[error("The capsule URL you provided is not a valid URL: {url}")]
     InvalidCapsuleUrl { url: String },
+
+    #[error("The `--since` date ({since}) is after the `--until` date ({until}).")]
+    InvalidDateRange { since: NaiveDate, until: NaiveDate },
 }
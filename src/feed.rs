@@ -1,13 +1,27 @@
 use std::cmp;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
-use chrono::{DateTime, FixedOffset, Local};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, Utc};
 use eyre::bail;
 use url::Url;
 
-use crate::config::{AuthorConfig, Config};
-use crate::entry::{Entry, PostLocation, PostLocationParams};
-use crate::template::{PostPathParams, PostPathTemplateData};
+use crate::config::{AuthorConfig, Config, MissingPublished, SortDirection, SortKey};
+use crate::entry::{
+    check_entry_dates, check_entry_ids, check_entry_preformatting, check_entry_values, Entry,
+    MetadataOptions, PostLocation, PostLocationParams, Translation,
+};
+use crate::error::Error;
+use crate::gemtext;
+use crate::slug::slugify;
+use crate::snippet::expand_includes;
+use crate::template::{
+    CategoryPathTemplateData, IndexPagePathTemplateData, PostPathParams, PostPathTemplateData,
+};
+
+// With pretty URLs, each post is the index file of its own directory.
+const DIRECTORY_INDEX_NAME: &str = "index";
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FeedAuthor {
@@ -26,82 +40,990 @@ impl From<AuthorConfig> for FeedAuthor {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Category {
+    pub name: String,
+    pub slug: String,
+    pub url: Url,
+    pub path: PathBuf,
+}
+
+/// A feed of only the posts in some categories, with its own Atom feed and index page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subfeed {
+    pub name: String,
+    pub title: String,
+    /// The slugs of the categories a post needs at least one of to be in this feed.
+    pub category_slugs: BTreeSet<String>,
+    /// When the most recently updated post in this feed was updated.
+    pub updated: DateTime<FixedOffset>,
+    pub feed_url: Url,
+    /// The URL path of the Atom feed.
+    pub feed_path: String,
+    /// The index page, which is never paginated.
+    pub index_page: IndexPage,
+}
+
+// Whether any of a post's categories has one of the given slugs.
+fn has_any_category(category_slugs: &BTreeSet<String>, categories: &[String]) -> bool {
+    categories
+        .iter()
+        .any(|name| category_slugs.contains(&slugify(name)))
+}
+
+impl Subfeed {
+    /// Whether a post with these categories belongs in this feed.
+    pub fn includes(&self, categories: &[String]) -> bool {
+        has_any_category(&self.category_slugs, categories)
+    }
+}
+
+/// One page of the paginated index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexPage {
+    /// The page number, starting at 1.
+    pub number: usize,
+    pub url: Url,
+    pub path: PathBuf,
+    /// The range of feed entries on this page.
+    pub entries: Range<usize>,
+}
+
+// Get the URL and the file path (relative to the public directory) of a page from its URL path.
+fn locate_page(capsule_url: &Url, page_path: &str) -> eyre::Result<PostLocation> {
+    let mut page_url = capsule_url.clone();
+
+    let mut url_segments = match page_url.path_segments_mut() {
+        Ok(segments) => segments,
+        Err(()) => bail!("capsule URL cannot be a base URL"),
+    };
+
+    let mut page_filepath = PathBuf::new();
+
+    for segment in page_path.split('/') {
+        url_segments.push(segment);
+        page_filepath.push(segment);
+    }
+
+    drop(url_segments);
+
+    Ok(PostLocation {
+        url: page_url,
+        path: page_filepath,
+    })
+}
+
+// Like `locate_page`, but the page is generated as the index file of a directory, so its URL can
+// end in a slash. A page which is already a directory index, like `/posts/foo/index.gmi`, stays
+// where it is.
+fn locate_pretty_page(
+    capsule_url: &Url,
+    page_path: &str,
+    gemtext_ext: &str,
+) -> eyre::Result<PostLocation> {
+    let (dir_path, file_name) = page_path.rsplit_once('/').unwrap_or(("", page_path));
+    let page_name = file_name
+        .strip_suffix(&format!(".{gemtext_ext}"))
+        .unwrap_or(file_name);
+
+    let dir_path = if page_name == DIRECTORY_INDEX_NAME {
+        dir_path.to_owned()
+    } else {
+        format!("{dir_path}/{page_name}")
+    };
+
+    // The trailing empty segment gives the URL its trailing slash.
+    let location = locate_page(capsule_url, &format!("{dir_path}/"))?;
+
+    Ok(PostLocation {
+        url: location.url,
+        path: location
+            .path
+            .join(format!("{DIRECTORY_INDEX_NAME}.{gemtext_ext}")),
+    })
+}
+
+/// Join a path onto the capsule URL.
+///
+/// Both absolute and relative paths are relative to the capsule URL, which might not be the root of
+/// the host. Empty path segments are removed, but a trailing slash is preserved.
+pub fn join_capsule_url(capsule_url: &Url, path: &str) -> Result<Url, String> {
+    let mut url = capsule_url.clone();
+
+    let mut segments = capsule_url
+        .path()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => continue,
+            ".." => return Err(format!("The path `{path}` cannot contain `..`.")),
+            _ => segments.push(segment.to_owned()),
+        }
+    }
+
+    let mut url_segments = url
+        .path_segments_mut()
+        .map_err(|()| String::from("The capsule URL cannot be a base URL."))?;
+
+    url_segments.clear();
+    url_segments.extend(&segments);
+
+    if path.ends_with('/') && !segments.is_empty() {
+        url_segments.push("");
+    }
+
+    drop(url_segments);
+
+    Ok(url)
+}
+
+// Resolve the URLs of an entry's enclosures. Absolute URLs are left alone, and relative URLs are
+// resolved against the capsule URL.
+fn resolve_enclosure_urls(entry: &mut Entry, capsule_url: &Url) -> eyre::Result<()> {
+    for enclosure in &mut entry.metadata.enclosures {
+        if Url::parse(&enclosure.url).is_ok() {
+            continue;
+        }
+
+        match join_capsule_url(capsule_url, &enclosure.url) {
+            Ok(url) => enclosure.url = url.to_string(),
+            Err(reason) => bail!(Error::InvalidMetadataFile {
+                path: entry.metadata_path.clone(),
+                reason: format!("The enclosure URL `{}` is invalid. {reason}", enclosure.url),
+            }),
+        }
+    }
+
+    Ok(())
+}
+
+// Resolve an optional URL from the config. Absolute URLs are left alone, and relative URLs are
+// resolved against the capsule URL.
+fn resolve_config_url(
+    capsule_url: &Url,
+    field: &str,
+    url: Option<&str>,
+) -> eyre::Result<Option<Url>> {
+    let url = match url {
+        Some(url) => url,
+        None => return Ok(None),
+    };
+
+    if let Ok(absolute_url) = Url::parse(url) {
+        return Ok(Some(absolute_url));
+    }
+
+    match join_capsule_url(capsule_url, url) {
+        Ok(resolved_url) => Ok(Some(resolved_url)),
+        Err(reason) => bail!(Error::InvalidConfigUrl {
+            field: field.to_owned(),
+            url: url.to_owned(),
+            reason,
+        }),
+    }
+}
+
+/// A post which has moved to another URL.
+///
+/// Instead of the post itself, we generate a page at the post's URL which links to its new URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub title: String,
+    pub url: Url,
+    pub path: PathBuf,
+    pub target: Url,
+}
+
+// Turn an entry which has a `redirect` into a redirect. Absolute URLs are left alone, and relative
+// URLs are resolved against the capsule URL.
+fn redirect_from_entry(entry: Entry, target: &str, capsule_url: &Url) -> eyre::Result<Redirect> {
+    let target = match Url::parse(target) {
+        Ok(url) => url,
+        Err(_) => match join_capsule_url(capsule_url, target) {
+            Ok(url) => url,
+            Err(reason) => bail!(Error::InvalidMetadataFile {
+                path: entry.metadata_path,
+                reason: format!("The redirect URL `{target}` is invalid. {reason}"),
+            }),
+        },
+    };
+
+    Ok(Redirect {
+        title: entry.metadata.title,
+        url: entry.url,
+        path: entry.path,
+        target,
+    })
+}
+
+// Make a redirect to each post from each of its aliases. Aliases can't be the same page as a post,
+// a redirect, or another alias.
+fn alias_redirects(
+    entries: &[Entry],
+    redirects: &[Redirect],
+    capsule_url: &Url,
+) -> eyre::Result<Vec<Redirect>> {
+    let mut taken_paths = entries
+        .iter()
+        .map(|entry| {
+            (
+                entry.path.clone(),
+                format!("the post at `{}`", entry.source_path.to_string_lossy()),
+            )
+        })
+        .chain(redirects.iter().map(|redirect| {
+            (
+                redirect.path.clone(),
+                format!("the redirect for `{}`", redirect.title),
+            )
+        }))
+        .collect::<HashMap<_, _>>();
+
+    let mut aliases = Vec::new();
+
+    for entry in entries {
+        for alias in &entry.metadata.aliases {
+            let location = locate_page(capsule_url, alias)?;
+
+            if let Some(existing) = taken_paths.get(&location.path) {
+                bail!(Error::InvalidMetadataFile {
+                    path: entry.metadata_path.clone(),
+                    reason: format!("The alias `{alias}` is the same page as {existing}."),
+                });
+            }
+
+            taken_paths.insert(
+                location.path.clone(),
+                format!("an alias of `{}`", entry.source_path.to_string_lossy()),
+            );
+
+            aliases.push(Redirect {
+                title: entry.metadata.title.clone(),
+                url: location.url,
+                path: location.path,
+                target: entry.url.clone(),
+            });
+        }
+    }
+
+    Ok(aliases)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum SortValue<'a> {
+    Time(DateTime<FixedOffset>),
+    Text(&'a str),
+}
+
+// Find where a post goes in the capsule, from either the `path` in its metadata file or the
+// `post_path` in the config.
+pub fn post_locator(
+    config: &Config,
+) -> impl Fn(PostLocationParams) -> eyre::Result<PostLocation> + '_ {
+    |params: PostLocationParams| -> eyre::Result<PostLocation> {
+        // A path in the metadata file takes precedence over the one in the config.
+        let post_path = match &params.metadata.path {
+            Some(post_path) => post_path.clone(),
+            None => PostPathTemplateData::from(PostPathParams {
+                slug: params.slug.to_owned(),
+                published: params.metadata.published,
+            })
+            .render(&config.post_path, config.url.as_str())?,
+        };
+
+        if config.pretty_urls {
+            locate_pretty_page(&config.url, &post_path, &config.gemtext_extension)
+        } else {
+            locate_page(&config.url, &post_path)
+        }
+    }
+}
+
+pub fn metadata_options(config: &Config) -> MetadataOptions {
+    MetadataOptions {
+        default_offset: config.default_timezone,
+        updated_from_mtime: config.updated_from_mtime,
+    }
+}
+
+fn sort_value(entry: &Entry, key: SortKey) -> SortValue<'_> {
+    let metadata = &entry.metadata;
+
+    match key {
+        SortKey::Published => SortValue::Time(metadata.published.unwrap_or(metadata.updated)),
+        SortKey::Updated => SortValue::Time(metadata.updated),
+        SortKey::Title => SortValue::Text(&metadata.title),
+        SortKey::Id => SortValue::Text(&metadata.id),
+    }
+}
+
+// Sort entries by the given key. Ties are broken by the post ID and then the source path so the
+// order is deterministic.
+fn sort_entries(entries: &mut [Entry], key: SortKey, direction: SortDirection) {
+    entries.sort_by(|a, b| {
+        let (a_value, b_value) = (sort_value(a, key), sort_value(b, key));
+
+        let by_key = match direction {
+            SortDirection::Ascending => a_value.cmp(&b_value),
+            SortDirection::Descending => b_value.cmp(&a_value),
+        };
+
+        by_key
+            .then_with(|| a.metadata.id.cmp(&b.metadata.id))
+            .then_with(|| a.source_path.cmp(&b.source_path))
+    });
+}
+
+// Sort unlisted entries, which aren't in chronological order, by their weight and then their
+// title. Entries without a weight come after those with one.
+fn sort_unlisted_entries(entries: &mut [Entry]) {
+    entries.sort_by(|a, b| {
+        let by_weight = match (a.metadata.weight, b.metadata.weight) {
+            (Some(a_weight), Some(b_weight)) => a_weight.cmp(&b_weight),
+            (Some(_), None) => cmp::Ordering::Less,
+            (None, Some(_)) => cmp::Ordering::Greater,
+            (None, None) => cmp::Ordering::Equal,
+        };
+
+        by_weight
+            .then_with(|| a.metadata.title.cmp(&b.metadata.title))
+            .then_with(|| a.source_path.cmp(&b.source_path))
+    });
+}
+
+// Link each entry to the other entries with the same translation key. Entries which don't specify a
+// language are in the capsule's default language.
+fn link_translations(entries: &mut [Entry], default_lang: Option<&str>) {
+    let mut groups = BTreeMap::<String, Vec<usize>>::new();
+
+    for (index, entry) in entries.iter().enumerate() {
+        if let Some(key) = &entry.metadata.translation_key {
+            groups.entry(key.clone()).or_default().push(index);
+        }
+    }
+
+    for indices in groups.into_values() {
+        let mut translations = indices
+            .iter()
+            .map(|&index| {
+                let entry = &entries[index];
+
+                Translation {
+                    lang: entry
+                        .metadata
+                        .lang
+                        .clone()
+                        .or_else(|| default_lang.map(ToOwned::to_owned)),
+                    url: entry.url.clone(),
+                    title: entry.metadata.title.clone(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Sort them so the languages are always listed in the same order.
+        translations.sort_by(|a, b| a.lang.cmp(&b.lang).then_with(|| a.url.cmp(&b.url)));
+
+        for &index in &indices {
+            let url = &entries[index].url;
+
+            entries[index].translations = translations
+                .iter()
+                .filter(|translation| translation.url != *url)
+                .cloned()
+                .collect();
+        }
+    }
+}
+
+/// Settings which affect which entries we include in the feed and how we validate them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FeedOptions {
+    /// Whether to include draft posts.
+    pub include_drafts: bool,
+
+    /// Whether to include posts which have expired.
+    pub ignore_expiry: bool,
+
+    /// The earliest date a post can be published on to be included.
+    pub since: Option<NaiveDate>,
+
+    /// The latest date a post can be published on to be included.
+    pub until: Option<NaiveDate>,
+
+    /// Whether to include posts without a publish date when `since` or `until` is set.
+    pub include_unpublished: bool,
+
+    /// Whether to warn rather than fail when a post was published after it was updated.
+    pub lenient_dates: bool,
+
+    /// Whether to fail rather than warn when a post is missing its gemtext or metadata file.
+    pub strict_post_files: bool,
+
+    /// Whether to warn about preformatted blocks which have no alt text.
+    pub warn_missing_alt_text: bool,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Feed {
     pub capsule_url: Url,
     pub feed_url: Url,
+    pub rss_url: Option<Url>,
+    pub gemsub_url: Option<Url>,
+    pub json_feed_url: Option<Url>,
     pub index_url: Url,
+    pub categories_url: Url,
+    pub archive_url: Url,
     pub title: String,
     pub updated: DateTime<FixedOffset>,
+    /// When the feed was assembled, which is the start of the build.
+    pub build_time: DateTime<FixedOffset>,
     pub subtitle: Option<String>,
     pub rights: Option<String>,
+    pub icon: Option<Url>,
+    pub logo: Option<Url>,
+    pub lang: Option<String>,
+    pub category_scheme: String,
+    pub includes_dir: Option<PathBuf>,
+    pub missing_published: MissingPublished,
+    /// How many of the most recent posts have their full content in the syndication feeds.
+    pub full_content_limit: Option<usize>,
     pub author: Option<FeedAuthor>,
+    pub words_per_minute: u32,
+    /// How long after a post was published it has to be updated to count as recently updated.
+    pub updated_threshold: Duration,
+    pub summary_length: usize,
+    pub plain_summaries: bool,
+    pub more_marker: String,
+    pub inline_links: bool,
+    pub breadcrumb_home: String,
+    /// The posts directory, which the source path of each entry is shown relative to.
+    pub posts_dir: PathBuf,
     pub entries: Vec<Entry>,
+    pub categories: Vec<Category>,
+    pub index_pages: Vec<IndexPage>,
+    pub subfeeds: Vec<Subfeed>,
+    pub redirects: Vec<Redirect>,
+    /// Posts which have a page but are left out of the feed.
+    pub unlisted_entries: Vec<Entry>,
+    /// The number of draft posts which were left out of the feed.
+    pub skipped_drafts: usize,
+    /// The number of expired posts which were left out of the feed.
+    pub skipped_expired: usize,
+    /// The number of posts which were left out of the feed because they weren't published in the
+    /// date range.
+    pub skipped_out_of_range: usize,
+}
+
+// Get the time the most recently updated post was updated. This only depends on the posts, not when
+// the feed was built, so rebuilding the same posts doesn't make the feed look updated to
+// subscribers. Drafts are left out, since they're only in the feed when previewing it.
+fn most_recent_update<'a>(entries: impl IntoIterator<Item = &'a Entry>) -> DateTime<FixedOffset> {
+    entries
+        .into_iter()
+        .filter(|entry| !entry.metadata.draft)
+        .map(|entry| entry.metadata.updated)
+        .max()
+        // With no posts, we use a fixed time rather than the current time for the same reason.
+        .unwrap_or(DateTime::UNIX_EPOCH.fixed_offset())
 }
 
 impl Feed {
-    pub fn from_config(config: &Config, warn_handler: impl Fn(&str)) -> eyre::Result<Self> {
-        let locator = |params: PostLocationParams| -> eyre::Result<PostLocation> {
-            let mut post_url = config.url.clone();
+    pub fn from_config(
+        config: &Config,
+        options: FeedOptions,
+        warn_handler: impl Fn(&str),
+    ) -> eyre::Result<Self> {
+        let entries = Entry::from_posts(
+            &config.posts_dir,
+            &config.post_files,
+            post_locator(config),
+            metadata_options(config),
+            options.strict_post_files,
+            &warn_handler,
+        )?;
 
-            let path_params = PostPathTemplateData::from(PostPathParams {
-                slug: params.slug.to_owned(),
-                published: params.metadata.published,
+        Self::from_entries(config, options, entries, warn_handler)
+    }
+
+    /// Assemble a feed with just one post, which doesn't need to be in the posts directory.
+    pub fn from_post(
+        config: &Config,
+        options: FeedOptions,
+        post_path: &Path,
+        post_contents: String,
+        metadata_path: Option<&Path>,
+        warn_handler: impl Fn(&str),
+    ) -> eyre::Result<Self> {
+        let entry = Entry::from_post(
+            post_path,
+            post_contents,
+            metadata_path,
+            post_locator(config),
+            metadata_options(config),
+        )?;
+
+        Self::from_entries(config, options, vec![entry], warn_handler)
+    }
+
+    fn from_entries(
+        config: &Config,
+        options: FeedOptions,
+        entries: Vec<Entry>,
+        warn_handler: impl Fn(&str),
+    ) -> eyre::Result<Self> {
+        let build_time = Utc::now().fixed_offset();
+
+        // We do not publish draft posts, unless the user asked us to.
+        let (drafts, entries): (Vec<_>, Vec<_>) = entries
+            .into_iter()
+            .partition(|entry| entry.metadata.draft && !options.include_drafts);
+
+        // Expired posts are left out just like drafts, unless the user asked us to keep them.
+        // This compares the instants, so it works regardless of the offset in the metadata file.
+        let now = Utc::now();
+        let (expired, entries): (Vec<_>, Vec<_>) = entries.into_iter().partition(|entry| {
+            !options.ignore_expiry && entry.metadata.expires.is_some_and(|expires| expires < now)
+        });
+
+        // Posts published outside the date range are left out too. This uses the date in the
+        // post's own offset, which is the date the author had in mind.
+        let (out_of_range, mut entries): (Vec<_>, Vec<_>) =
+            entries.into_iter().partition(|entry| {
+                if options.since.is_none() && options.until.is_none() {
+                    return false;
+                }
+
+                match entry
+                    .metadata
+                    .published
+                    .map(|published| published.date_naive())
+                {
+                    Some(date) => {
+                        options.since.is_some_and(|since| date < since)
+                            || options.until.is_some_and(|until| date > until)
+                    }
+                    None => !options.include_unpublished,
+                }
             });
 
-            let post_path = path_params.render(&config.post_path)?;
+        check_entry_ids(&entries, &warn_handler)?;
+        check_entry_dates(&entries, options.lenient_dates, &warn_handler)?;
 
-            let mut url_segments = match post_url.path_segments_mut() {
-                Ok(segments) => segments,
-                Err(()) => bail!("capsule URL cannot be a base URL"),
-            };
+        if let Some(values_schema) = &config.values_schema {
+            check_entry_values(&entries, values_schema)?;
+        }
 
-            let mut post_filepath = PathBuf::new();
+        for entry in &mut entries {
+            resolve_enclosure_urls(entry, &config.url)?;
+        }
 
-            for segment in post_path.split('/') {
-                url_segments.push(segment);
-                post_filepath.push(segment);
+        if let Some(snippets_dir) = &config.snippets_dir {
+            for entry in &mut entries {
+                entry.body = expand_includes(&entry.body, snippets_dir).map_err(|reason| {
+                    Error::InvalidPostInclude {
+                        path: entry.source_path.clone(),
+                        reason,
+                    }
+                })?;
             }
+        }
 
-            drop(url_segments);
+        // Headings in included snippets are shifted along with the rest of the post.
+        for entry in &mut entries {
+            let (body, clamped) =
+                gemtext::shift_headings(&entry.body, entry.metadata.heading_shift);
 
-            Ok(PostLocation {
-                url: post_url,
-                path: post_filepath,
-            })
-        };
+            if clamped > 0 {
+                warn_handler(&format!(
+                    "Gemtext only has three levels of headings, so {clamped} heading(s) in this post were shifted to the third level instead: {}",
+                    entry.source_path.to_string_lossy()
+                ));
+            }
 
-        let mut entries = Entry::from_posts(&config.posts_dir, locator, warn_handler)?;
+            entry.body = body;
+        }
 
-        // Sort entries in reverse-chronological order by publish time or, if there is no publish
-        // time by last updated time.
-        entries.sort_by_key(|entry| {
-            cmp::Reverse(entry.metadata.published.unwrap_or(entry.metadata.updated))
-        });
+        // This comes after expanding includes, since a snippet could open or close a block.
+        check_entry_preformatting(&entries, options.warn_missing_alt_text, &warn_handler)?;
 
-        // Get the time the most recently updated post was updated.
-        let last_updated = entries
-            .iter()
-            .max_by_key(|entry| entry.metadata.updated)
-            .map(|entry| entry.metadata.updated)
-            .unwrap_or_else(|| Local::now().fixed_offset());
+        // Posts which have moved are not part of the feed; we only generate a redirect page for
+        // them.
+        let mut redirects = Vec::new();
+        let mut published_entries = Vec::with_capacity(entries.len());
+
+        for entry in entries {
+            match entry.metadata.redirect.clone() {
+                Some(target) => redirects.push(redirect_from_entry(entry, &target, &config.url)?),
+                None => published_entries.push(entry),
+            }
+        }
+
+        // Old URLs of posts redirect to them.
+        let aliases = alias_redirects(&published_entries, &redirects, &config.url)?;
+        redirects.extend(aliases);
+
+        // Unlisted posts can still be translations of listed ones, so we link them first.
+        link_translations(&mut published_entries, config.lang.as_deref());
+
+        // Unlisted posts still get a page, but they're not part of the feed either.
+        let (mut unlisted_entries, mut entries): (Vec<_>, Vec<_>) = published_entries
+            .into_iter()
+            .partition(|entry| entry.metadata.unlisted);
+
+        sort_unlisted_entries(&mut unlisted_entries);
+
+        sort_entries(&mut entries, config.sort, config.sort_direction);
+
+        // Pinned posts come before the rest. The sort is stable, so they stay in the same order
+        // among themselves.
+        entries.sort_by_key(|entry| !entry.metadata.pinned);
+
+        let last_updated = config
+            .feed_updated
+            .unwrap_or_else(|| most_recent_update(&entries));
+
+        // Collect the distinct categories across all entries. Categories are distinct by their slug,
+        // and we use the first name we encounter for each category.
+        let mut category_names = BTreeMap::new();
+
+        for entry in &entries {
+            for name in &entry.metadata.categories {
+                let slug = slugify(name);
+
+                if slug.is_empty() {
+                    warn_handler(&format!(
+                        "This category cannot be turned into a URL, so it will not have a category page: {name}"
+                    ));
+                    continue;
+                }
+
+                category_names.entry(slug).or_insert_with(|| name.clone());
+            }
+        }
+
+        let mut categories = Vec::with_capacity(category_names.len());
+
+        for (slug, name) in category_names {
+            let path_params = CategoryPathTemplateData { slug: slug.clone() };
+            let category_path = path_params.render(&config.category_path, config.url.as_str())?;
+            let location = locate_page(&config.url, &category_path)?;
+
+            categories.push(Category {
+                name,
+                slug,
+                url: location.url,
+                path: location.path,
+            });
+        }
+
+        // Split the entries into pages for the index. If pagination is disabled, there is only one
+        // page. There is always at least one page, even if there are no entries.
+        let per_page = config.per_page.unwrap_or(entries.len()).max(1);
+        let page_count = entries.len().div_ceil(per_page).max(1);
+        let mut index_pages = Vec::with_capacity(page_count);
+
+        for page_index in 0..page_count {
+            let number = page_index + 1;
+
+            // The first page is always at the index path.
+            let page_path = if number == 1 {
+                config.index_path.clone()
+            } else {
+                IndexPagePathTemplateData { page: number }
+                    .render(&config.index_page_path, config.url.as_str())?
+            };
+
+            let location = locate_page(&config.url, &page_path)?;
+            let start = page_index * per_page;
+
+            index_pages.push(IndexPage {
+                number,
+                url: location.url,
+                path: location.path,
+                entries: start..cmp::min(start + per_page, entries.len()),
+            });
+        }
+
+        let mut subfeeds = Vec::with_capacity(config.subfeeds.len());
+
+        for (name, subfeed_config) in &config.subfeeds {
+            let category_slugs = subfeed_config
+                .categories
+                .iter()
+                .map(|name| slugify(name))
+                .collect::<BTreeSet<_>>();
+
+            let subfeed_entries = entries
+                .iter()
+                .filter(|entry| has_any_category(&category_slugs, &entry.metadata.categories))
+                .collect::<Vec<_>>();
+
+            // The subfeed was last updated when one of its own posts was.
+            let updated = config
+                .feed_updated
+                .unwrap_or_else(|| most_recent_update(subfeed_entries.iter().copied()));
+
+            let mut feed_url = config.url.clone();
+            feed_url.set_path(&subfeed_config.feed_path);
+
+            let location = locate_page(&config.url, &subfeed_config.index_path)?;
+
+            subfeeds.push(Subfeed {
+                name: name.clone(),
+                title: subfeed_config
+                    .title
+                    .clone()
+                    .unwrap_or_else(|| config.title.clone()),
+                category_slugs,
+                updated,
+                feed_url,
+                feed_path: subfeed_config.feed_path.clone(),
+                index_page: IndexPage {
+                    number: 1,
+                    url: location.url,
+                    path: location.path,
+                    entries: 0..subfeed_entries.len(),
+                },
+            });
+        }
+
+        let mut categories_url = config.url.clone();
+        categories_url.set_path(&config.categories_path);
+
+        let mut archive_url = config.url.clone();
+        archive_url.set_path(&config.archive_path);
 
         let mut feed_url = config.url.clone();
         feed_url.set_path(&config.feed_path);
 
+        // The RSS feed is only generated when the user has configured a path for it.
+        let rss_url = config.rss_path.as_ref().map(|rss_path| {
+            let mut rss_url = config.url.clone();
+            rss_url.set_path(rss_path);
+            rss_url
+        });
+
+        // Same for the gemsub feed.
+        let gemsub_url = config.gemsub_path.as_ref().map(|gemsub_path| {
+            let mut gemsub_url = config.url.clone();
+            gemsub_url.set_path(gemsub_path);
+            gemsub_url
+        });
+
+        // Same for the JSON Feed.
+        let json_feed_url = config.json_feed_path.as_ref().map(|json_feed_path| {
+            let mut json_feed_url = config.url.clone();
+            json_feed_url.set_path(json_feed_path);
+            json_feed_url
+        });
+
         let mut index_url = config.url.clone();
         index_url.set_path(&config.index_path);
 
+        let icon = resolve_config_url(&config.url, "icon", config.icon.as_deref())?;
+        let logo = resolve_config_url(&config.url, "logo", config.logo.as_deref())?;
+
         Ok(Feed {
             capsule_url: config.url.clone(),
             feed_url,
+            rss_url,
+            gemsub_url,
+            json_feed_url,
             index_url,
+            categories_url,
+            archive_url,
             title: config.title.clone(),
             updated: last_updated,
+            build_time,
             subtitle: config.subtitle.clone(),
             rights: config.rights.clone(),
+            icon,
+            logo,
+            lang: config.lang.clone(),
+            // Category names are only meaningful within this capsule, unless the user says
+            // otherwise.
+            category_scheme: config
+                .category_scheme
+                .clone()
+                .unwrap_or_else(|| config.url.to_string()),
+            includes_dir: config.includes_dir.clone(),
+            missing_published: config.missing_published,
+            full_content_limit: config.feed_full_content,
             author: config.author.as_ref().cloned().map(Into::into),
+            words_per_minute: config.words_per_minute,
+            updated_threshold: Duration::days(config.updated_threshold_days.into()),
+            summary_length: config.summary_length,
+            plain_summaries: config.plain_summaries,
+            more_marker: config.more_marker.clone(),
+            inline_links: config.inline_links,
+            breadcrumb_home: config.breadcrumb_home.clone(),
+            posts_dir: config.posts_dir.clone(),
             entries,
+            categories,
+            index_pages,
+            subfeeds,
+            redirects,
+            unlisted_entries,
+            skipped_drafts: drafts.len(),
+            skipped_expired: expired.len(),
+            skipped_out_of_range: out_of_range.len(),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::EntryMetadata;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn joins_onto_base_with_trailing_slash() {
+        let capsule_url = url("gemini://example.com/~user/");
+
+        assert_eq!(
+            join_capsule_url(&capsule_url, "posts/hello.gmi"),
+            Ok(url("gemini://example.com/~user/posts/hello.gmi"))
+        );
+    }
+
+    #[test]
+    fn joins_onto_base_without_trailing_slash() {
+        let capsule_url = url("gemini://example.com/~user");
+
+        assert_eq!(
+            join_capsule_url(&capsule_url, "posts/hello.gmi"),
+            Ok(url("gemini://example.com/~user/posts/hello.gmi"))
+        );
+        assert_eq!(
+            join_capsule_url(&capsule_url, "posts/"),
+            Ok(url("gemini://example.com/~user/posts/"))
+        );
+    }
+
+    #[test]
+    fn joins_path_with_leading_slash_relative_to_capsule() {
+        let capsule_url = url("gemini://example.com/~user/");
+
+        assert_eq!(
+            join_capsule_url(&capsule_url, "/posts//hello.gmi"),
+            Ok(url("gemini://example.com/~user/posts/hello.gmi"))
+        );
+        assert_eq!(
+            join_capsule_url(&capsule_url, "/"),
+            Ok(url("gemini://example.com/~user/"))
+        );
+    }
+
+    #[test]
+    fn rejects_parent_segments() {
+        let capsule_url = url("gemini://example.com/~user/");
+
+        assert!(join_capsule_url(&capsule_url, "../other/secret.gmi").is_err());
+        assert!(join_capsule_url(&capsule_url, "/posts/../../x").is_err());
+    }
+
+    fn entry(source: &str, front_matter: &str) -> Entry {
+        let source_path = PathBuf::from(source);
+        let options = MetadataOptions {
+            default_offset: FixedOffset::east_opt(0).unwrap(),
+            updated_from_mtime: false,
+        };
+        let metadata =
+            EntryMetadata::from_front_matter(front_matter, &source_path, options).unwrap();
+
+        Entry {
+            metadata,
+            body: String::new(),
+            url: url(&format!("gemini://example.com/{source}")),
+            path: source_path.clone(),
+            source_path: source_path.clone(),
+            file_modified: None,
+            metadata_path: source_path,
+            translations: Vec::new(),
+        }
+    }
+
+    fn sorted_sources(entries: &[Entry], key: SortKey, direction: SortDirection) -> Vec<String> {
+        let mut entries = entries.to_vec();
+        sort_entries(&mut entries, key, direction);
+
+        entries
+            .iter()
+            .map(|entry| entry.source_path.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn sorts_by_published_falling_back_to_updated() {
+        let entries = [
+            entry(
+                "old.gmi",
+                "id: a\ntitle: Old\npublished: 2024-01-01\nupdated: 2024-06-01",
+            ),
+            entry(
+                "unpublished.gmi",
+                "id: b\ntitle: Unpublished\nupdated: 2024-03-01",
+            ),
+            entry(
+                "new.gmi",
+                "id: c\ntitle: New\npublished: 2024-05-01\nupdated: 2024-05-01",
+            ),
+        ];
+
+        assert_eq!(
+            sorted_sources(&entries, SortKey::Published, SortDirection::Descending),
+            ["new.gmi", "unpublished.gmi", "old.gmi"]
+        );
+        assert_eq!(
+            sorted_sources(&entries, SortKey::Published, SortDirection::Ascending),
+            ["old.gmi", "unpublished.gmi", "new.gmi"]
+        );
+        assert_eq!(
+            sorted_sources(&entries, SortKey::Updated, SortDirection::Descending),
+            ["old.gmi", "new.gmi", "unpublished.gmi"]
+        );
+    }
+
+    #[test]
+    fn sorts_by_text_keys() {
+        let entries = [
+            entry("one.gmi", "id: c\ntitle: Banana\nupdated: 2024-01-01"),
+            entry("two.gmi", "id: a\ntitle: Cherry\nupdated: 2024-01-01"),
+            entry("three.gmi", "id: b\ntitle: Apple\nupdated: 2024-01-01"),
+        ];
+
+        assert_eq!(
+            sorted_sources(&entries, SortKey::Title, SortDirection::Ascending),
+            ["three.gmi", "one.gmi", "two.gmi"]
+        );
+        assert_eq!(
+            sorted_sources(&entries, SortKey::Id, SortDirection::Descending),
+            ["one.gmi", "three.gmi", "two.gmi"]
+        );
+    }
+
+    #[test]
+    fn breaks_ties_by_id_then_source_path() {
+        let entries = [
+            entry("b.gmi", "id: same\ntitle: Post\nupdated: 2024-01-01"),
+            entry("a.gmi", "id: same\ntitle: Post\nupdated: 2024-01-01"),
+            entry("c.gmi", "id: first\ntitle: Post\nupdated: 2024-01-01"),
+        ];
+
+        // Ties are broken in the same order whichever direction the key sorts in.
+        for direction in [SortDirection::Ascending, SortDirection::Descending] {
+            assert_eq!(
+                sorted_sources(&entries, SortKey::Updated, direction),
+                ["c.gmi", "a.gmi", "b.gmi"]
+            );
+        }
+    }
+}
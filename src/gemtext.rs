@@ -0,0 +1,773 @@
+const PREFORMAT_TOGGLE: &str = "```";
+
+/// A single line of a gemtext document.
+///
+/// https://geminiprotocol.net/docs/gemtext-specification.gmi
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line<'a> {
+    Text(&'a str),
+    Link { url: &'a str, text: Option<&'a str> },
+    Heading { level: u8, text: &'a str },
+    ListItem(&'a str),
+    Quote(&'a str),
+    PreformatToggle { alt: &'a str },
+    Preformatted(&'a str),
+}
+
+impl<'a> Line<'a> {
+    /// The human-readable text of this line, sans any gemtext markup.
+    ///
+    /// Preformatted lines and toggle lines have no readable text.
+    pub fn text(&self) -> Option<&'a str> {
+        match self {
+            Line::Text(text) | Line::ListItem(text) | Line::Quote(text) => Some(text),
+            Line::Heading { text, .. } => Some(text),
+            Line::Link { text, .. } => *text,
+            Line::PreformatToggle { .. } | Line::Preformatted(_) => None,
+        }
+    }
+}
+
+fn parse_line(line: &str) -> Line<'_> {
+    if let Some(rest) = line.strip_prefix("=>") {
+        let rest = rest.trim_start();
+
+        return match rest.split_once(char::is_whitespace) {
+            Some((url, text)) => {
+                let text = text.trim();
+                Line::Link {
+                    url,
+                    text: if text.is_empty() { None } else { Some(text) },
+                }
+            }
+            None => Line::Link {
+                url: rest.trim_end(),
+                text: None,
+            },
+        };
+    }
+
+    if let Some(text) = line.strip_prefix("###") {
+        return Line::Heading {
+            level: 3,
+            text: text.trim(),
+        };
+    }
+
+    if let Some(text) = line.strip_prefix("##") {
+        return Line::Heading {
+            level: 2,
+            text: text.trim(),
+        };
+    }
+
+    if let Some(text) = line.strip_prefix('#') {
+        return Line::Heading {
+            level: 1,
+            text: text.trim(),
+        };
+    }
+
+    if let Some(text) = line.strip_prefix("* ") {
+        return Line::ListItem(text.trim());
+    }
+
+    if let Some(text) = line.strip_prefix('>') {
+        return Line::Quote(text.trim());
+    }
+
+    Line::Text(line)
+}
+
+/// Parse a gemtext document into its lines.
+pub fn parse(body: &str) -> Vec<Line<'_>> {
+    let mut lines = Vec::new();
+    let mut preformatted = false;
+
+    for line in body.lines() {
+        if let Some(alt) = line.strip_prefix(PREFORMAT_TOGGLE) {
+            preformatted = !preformatted;
+            lines.push(Line::PreformatToggle { alt: alt.trim() });
+        } else if preformatted {
+            lines.push(Line::Preformatted(line));
+        } else {
+            lines.push(parse_line(line));
+        }
+    }
+
+    lines
+}
+
+/// A preformatted block in a gemtext document, between an opening and closing toggle line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreformattedBlock<'a> {
+    /// The alt text after the opening toggle, if there is any.
+    pub alt: Option<&'a str>,
+    pub content: String,
+    /// The line number of the opening toggle, starting from 1.
+    pub line: usize,
+    /// Whether the block has a closing toggle, rather than running to the end of the document.
+    pub closed: bool,
+}
+
+/// Get the preformatted blocks from a parsed gemtext document, in order.
+pub fn preformatted_blocks<'a>(lines: &[Line<'a>]) -> Vec<PreformattedBlock<'a>> {
+    let mut blocks = Vec::new();
+
+    // The alt text, the line number of the opening toggle, and the lines of the open block.
+    let mut current: Option<(&str, usize, Vec<&str>)> = None;
+
+    let to_block = |(alt, line, content): (&'a str, usize, Vec<&str>), closed| PreformattedBlock {
+        alt: if alt.is_empty() { None } else { Some(alt) },
+        content: content.join("\n"),
+        line,
+        closed,
+    };
+
+    for (index, line) in lines.iter().enumerate() {
+        match (line, &mut current) {
+            // Alt text on the closing toggle has no meaning, so we ignore it.
+            (Line::PreformatToggle { .. }, Some(_)) => {
+                blocks.extend(current.take().map(|block| to_block(block, true)));
+            }
+            (Line::PreformatToggle { alt }, None) => {
+                current = Some((alt, index + 1, Vec::new()));
+            }
+            (Line::Preformatted(text), Some((_, _, content))) => content.push(text),
+            _ => {}
+        }
+    }
+
+    blocks.extend(current.map(|block| to_block(block, false)));
+
+    blocks
+}
+
+/// Count the words in a parsed gemtext document.
+///
+/// This ignores gemtext markup like link URLs and excludes preformatted text entirely.
+pub fn word_count(lines: &[Line]) -> usize {
+    lines
+        .iter()
+        .filter_map(Line::text)
+        .map(|text| text.split_whitespace().count())
+        .sum()
+}
+
+/// The estimated time to read a document, in minutes.
+///
+/// This is rounded to the nearest minute, but any non-empty document takes at least a minute.
+pub fn reading_time(word_count: usize, words_per_minute: u32) -> u32 {
+    if word_count == 0 {
+        return 0;
+    }
+
+    let words_per_minute = words_per_minute as usize;
+    let minutes = (word_count + words_per_minute / 2) / words_per_minute;
+
+    u32::try_from(minutes).unwrap_or(u32::MAX).max(1)
+}
+
+/// The first paragraph of plain text in a parsed gemtext document.
+///
+/// This skips headings, links, lists, quotes, and preformatted text.
+pub fn first_paragraph<'a>(lines: &[Line<'a>]) -> Option<&'a str> {
+    lines.iter().find_map(|line| match line {
+        Line::Text(text) if !text.trim().is_empty() => Some(text.trim()),
+        _ => None,
+    })
+}
+
+/// Truncate text to at most `max_chars` characters at a word boundary, adding an ellipsis if the
+/// text was truncated.
+pub fn truncate_words(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_owned();
+    }
+
+    // Leave room for the ellipsis.
+    let max_chars = max_chars.saturating_sub(1);
+    let mut truncated = String::new();
+    let mut truncated_len = 0;
+
+    for word in text.split_whitespace() {
+        let word_len = word.chars().count();
+        let separator_len = if truncated.is_empty() { 0 } else { 1 };
+
+        if truncated_len + separator_len + word_len > max_chars {
+            break;
+        }
+
+        if separator_len > 0 {
+            truncated.push(' ');
+        }
+
+        truncated.push_str(word);
+        truncated_len += separator_len + word_len;
+    }
+
+    // If even the first word is too long, we have no choice but to cut it off.
+    if truncated.is_empty() {
+        truncated = text.chars().take(max_chars).collect();
+    }
+
+    truncated.push('…');
+
+    truncated
+}
+
+/// Convert a gemtext document to plain text by removing its markup.
+///
+/// Links become their label followed by their URL in parentheses, or just the URL if they have no
+/// label. Heading, list, and quote markers are removed, as are the lines which start and end
+/// preformatted text, but the preformatted text itself is kept.
+pub fn to_plain_text(body: &str) -> String {
+    parse(body)
+        .into_iter()
+        .filter_map(|line| match line {
+            Line::Link {
+                url,
+                text: Some(text),
+            } => Some(format!("{text} ({url})")),
+            Line::Link { url, text: None } => Some(url.to_owned()),
+            Line::PreformatToggle { .. } => None,
+            Line::Preformatted(text) => Some(text.to_owned()),
+            _ => line.text().map(ToOwned::to_owned),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Split a gemtext document into a teaser and the full document at a marker line.
+///
+/// This returns the document with every marker line removed, along with everything before the
+/// first marker line, if there is one. Marker lines in preformatted text are left alone.
+pub fn split_teaser(body: &str, marker: &str) -> (String, Option<String>) {
+    let marker = marker.trim();
+    let mut full = String::with_capacity(body.len());
+    let mut teaser = None;
+    let mut preformatted = false;
+
+    for line in body.lines() {
+        if line.starts_with(PREFORMAT_TOGGLE) {
+            preformatted = !preformatted;
+        }
+
+        if !preformatted && line.trim() == marker {
+            if teaser.is_none() {
+                teaser = Some(full.trim_end().to_owned());
+            }
+
+            continue;
+        }
+
+        full.push_str(line);
+        full.push('\n');
+    }
+
+    match teaser {
+        Some(teaser) => (full, Some(teaser)),
+        None => (body.to_owned(), None),
+    }
+}
+
+// Runs of more than this many blank lines are collapsed into one.
+const MAX_BLANK_LINES: usize = 2;
+
+// Write out a run of blank lines, collapsing it if it's too long.
+fn flush_blank_lines(normalized: &mut String, blank_lines: &mut usize) {
+    let kept = if *blank_lines > MAX_BLANK_LINES {
+        1
+    } else {
+        *blank_lines
+    };
+
+    for _ in 0..kept {
+        normalized.push('\n');
+    }
+
+    *blank_lines = 0;
+}
+
+/// Tidy up the whitespace in a gemtext document.
+///
+/// This removes trailing whitespace from each line and collapses runs of three or more blank lines
+/// into a single blank line. Preformatted text, including the lines which start and end it, is
+/// left exactly as it is.
+pub fn normalize_whitespace(body: &str) -> String {
+    let mut normalized = String::with_capacity(body.len());
+    let mut blank_lines = 0;
+    let mut preformatted = false;
+
+    for line in body.lines() {
+        let is_toggle = line.starts_with(PREFORMAT_TOGGLE);
+
+        if preformatted || is_toggle {
+            flush_blank_lines(&mut normalized, &mut blank_lines);
+            normalized.push_str(line);
+            normalized.push('\n');
+
+            if is_toggle {
+                preformatted = !preformatted;
+            }
+
+            continue;
+        }
+
+        let line = line.trim_end();
+
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+
+        flush_blank_lines(&mut normalized, &mut blank_lines);
+        normalized.push_str(line);
+        normalized.push('\n');
+    }
+
+    flush_blank_lines(&mut normalized, &mut blank_lines);
+
+    if !body.ends_with('\n') {
+        normalized.pop();
+    }
+
+    normalized
+}
+
+// Gemtext only has three levels of headings.
+const MAX_HEADING_LEVEL: usize = 3;
+
+/// Shift every heading in a gemtext document down by some number of levels, so `#` becomes `##`.
+///
+/// Headings which would go past the last level stay at the last level instead. This returns the
+/// shifted document and the number of headings which were clamped like this. Preformatted text is
+/// left alone.
+pub fn shift_headings(body: &str, shift: usize) -> (String, usize) {
+    if shift == 0 {
+        return (body.to_owned(), 0);
+    }
+
+    let mut shifted = String::with_capacity(body.len());
+    let mut clamped = 0;
+    let mut preformatted = false;
+
+    for line in body.lines() {
+        if line.starts_with(PREFORMAT_TOGGLE) {
+            preformatted = !preformatted;
+        }
+
+        let level = line
+            .bytes()
+            .take(MAX_HEADING_LEVEL)
+            .take_while(|&byte| byte == b'#')
+            .count();
+
+        if preformatted || level == 0 {
+            shifted.push_str(line);
+            shifted.push('\n');
+            continue;
+        }
+
+        if level + shift > MAX_HEADING_LEVEL {
+            clamped += 1;
+        }
+
+        let new_level = (level + shift).min(MAX_HEADING_LEVEL);
+
+        shifted.push_str(&"#".repeat(new_level));
+        shifted.push_str(&line[level..]);
+        shifted.push('\n');
+    }
+
+    if !body.ends_with('\n') {
+        shifted.pop();
+    }
+
+    (shifted, clamped)
+}
+
+const INLINE_LINK_START: &str = "[[";
+const INLINE_LINK_END: &str = "]]";
+
+// Parse the inside of an inline link marker, which looks like `label|url`.
+fn parse_inline_link(marker: &str) -> Option<(&str, &str)> {
+    let (label, url) = marker.split_once('|')?;
+    let (label, url) = (label.trim(), url.trim());
+
+    if label.is_empty() || url.is_empty() || url.contains(char::is_whitespace) {
+        return None;
+    }
+
+    Some((label, url))
+}
+
+/// Expand inline links in a gemtext document into footnote-style references.
+///
+/// Gemtext doesn't have inline links, so this replaces each `[[label|url]]` marker with `label[n]`
+/// and appends a numbered link line for each one at the end of the document. Markers in link lines
+/// and preformatted text are left alone, as are markers which aren't well-formed.
+pub fn expand_inline_links(body: &str) -> String {
+    if !body.contains(INLINE_LINK_START) {
+        return body.to_owned();
+    }
+
+    let mut expanded = String::with_capacity(body.len());
+    let mut links = Vec::new();
+    let mut preformatted = false;
+
+    for line in body.lines() {
+        if line.starts_with(PREFORMAT_TOGGLE) {
+            preformatted = !preformatted;
+        }
+
+        if preformatted || line.starts_with(PREFORMAT_TOGGLE) || line.starts_with("=>") {
+            expanded.push_str(line);
+            expanded.push('\n');
+            continue;
+        }
+
+        let mut rest = line;
+
+        while let Some(start) = rest.find(INLINE_LINK_START) {
+            let after_start = &rest[start + INLINE_LINK_START.len()..];
+
+            let (end, (label, url)) = match after_start
+                .find(INLINE_LINK_END)
+                .and_then(|end| parse_inline_link(&after_start[..end]).map(|link| (end, link)))
+            {
+                Some(link) => link,
+                None => {
+                    // This isn't an inline link, so we keep the brackets as they are.
+                    expanded.push_str(&rest[..start + INLINE_LINK_START.len()]);
+                    rest = after_start;
+                    continue;
+                }
+            };
+
+            links.push((url, label));
+            expanded.push_str(&rest[..start]);
+            expanded.push_str(&format!("{label}[{}]", links.len()));
+            rest = &after_start[end + INLINE_LINK_END.len()..];
+        }
+
+        expanded.push_str(rest);
+        expanded.push('\n');
+    }
+
+    if links.is_empty() {
+        return body.to_owned();
+    }
+
+    // Make sure the links don't end up in an unterminated preformatted block.
+    if preformatted {
+        expanded.push_str(PREFORMAT_TOGGLE);
+        expanded.push('\n');
+    }
+
+    expanded.push_str("\n## Links\n\n");
+
+    for (index, (url, label)) in links.into_iter().enumerate() {
+        expanded.push_str(&format!("=> {url} [{}] {label}\n", index + 1));
+    }
+
+    expanded
+}
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+// Characters which are drawn as part of the character before them, like combining marks, variation
+// selectors, and emoji skin tone modifiers, so they don't take up any space of their own.
+fn is_zero_width(c: char) -> bool {
+    unicode_normalization::char::is_combining_mark(c)
+        || matches!(c,
+            '\u{200B}'..='\u{200F}'
+            | '\u{FE00}'..='\u{FE0F}'
+            | '\u{1F3FB}'..='\u{1F3FF}'
+            | '\u{E0020}'..='\u{E007F}'
+            | '\u{E0100}'..='\u{E01EF}')
+}
+
+// Characters which take up two columns in a terminal, like CJK characters and most emoji. This is
+// the bulk of the East Asian Wide and Fullwidth ranges from Unicode, not all of them.
+fn is_wide(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{115F}'
+        | '\u{231A}'..='\u{231B}'
+        | '\u{2329}'..='\u{232A}'
+        | '\u{23E9}'..='\u{23EC}'
+        | '\u{25FD}'..='\u{25FE}'
+        | '\u{2614}'..='\u{2615}'
+        | '\u{26AA}'..='\u{26AB}'
+        | '\u{2705}'
+        | '\u{2728}'
+        | '\u{274C}'
+        | '\u{2753}'..='\u{2755}'
+        | '\u{2757}'
+        | '\u{2795}'..='\u{2797}'
+        | '\u{2B1B}'..='\u{2B1C}'
+        | '\u{2B50}'
+        | '\u{2B55}'
+        | '\u{2E80}'..='\u{303E}'
+        | '\u{3041}'..='\u{33FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{A000}'..='\u{A4CF}'
+        | '\u{A960}'..='\u{A97F}'
+        | '\u{AC00}'..='\u{D7A3}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FE10}'..='\u{FE19}'
+        | '\u{FE30}'..='\u{FE6F}'
+        | '\u{FF00}'..='\u{FF60}'
+        | '\u{FFE0}'..='\u{FFE6}'
+        | '\u{1F004}'
+        | '\u{1F0CF}'
+        | '\u{1F18E}'
+        | '\u{1F191}'..='\u{1F19A}'
+        | '\u{1F1E6}'..='\u{1F1FF}'
+        | '\u{1F200}'..='\u{1F251}'
+        | '\u{1F300}'..='\u{1F64F}'
+        | '\u{1F680}'..='\u{1F6FF}'
+        | '\u{1F7E0}'..='\u{1F7EB}'
+        | '\u{1F900}'..='\u{1F9FF}'
+        | '\u{1FA70}'..='\u{1FAFF}'
+        | '\u{20000}'..='\u{2FFFD}'
+        | '\u{30000}'..='\u{3FFFD}')
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    matches!(c, '\u{1F1E6}'..='\u{1F1FF}')
+}
+
+// The number of columns a reader sees some text take up. Characters joined into one emoji with a
+// zero width joiner, like a family, take up the space of the first one, and so does the second
+// letter of a flag.
+fn display_len(text: &str) -> usize {
+    let mut len = 0;
+    let mut joined = false;
+    let mut flag_started = false;
+
+    for c in text.chars() {
+        // The joiner itself is zero width, and so is the second letter of a flag.
+        let char_len = if joined || is_zero_width(c) || (is_regional_indicator(c) && flag_started) {
+            0
+        } else if is_wide(c) {
+            2
+        } else {
+            1
+        };
+
+        flag_started = is_regional_indicator(c) && !flag_started;
+        joined = c == ZERO_WIDTH_JOINER;
+        len += char_len;
+    }
+
+    len
+}
+
+// Whether a line starting with this word would be read as something other than plain text.
+fn starts_markup(word: &str) -> bool {
+    word.starts_with("=>")
+        || word.starts_with('#')
+        || word.starts_with('>')
+        || word.starts_with(PREFORMAT_TOGGLE)
+        || word == "*"
+}
+
+// Wrap the text of a line at word boundaries, starting each line with the prefix. The whitespace
+// between words is kept as it is, except where the line is broken.
+fn wrap_text(wrapped: &mut String, prefix: &str, text: &str, width: usize) {
+    let mut line = String::from(prefix);
+    let mut line_len = display_len(prefix);
+    let mut is_empty = true;
+    let mut space = "";
+    let mut rest = text.trim_start();
+
+    while !rest.is_empty() {
+        let word_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let (word, after_word) = rest.split_at(word_end);
+        let word_len = display_len(word);
+        let space_len = display_len(space);
+
+        // A word which would start a line with gemtext markup has to stay on the line before it,
+        // even if that makes the line too long.
+        if !is_empty && line_len + space_len + word_len > width && !starts_markup(word) {
+            wrapped.push_str(&line);
+            wrapped.push('\n');
+            line = String::from(prefix);
+            line_len = display_len(prefix);
+            is_empty = true;
+        }
+
+        if !is_empty {
+            line.push_str(space);
+            line_len += space_len;
+        }
+
+        line.push_str(word);
+        line_len += word_len;
+        is_empty = false;
+
+        let space_end = after_word
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(after_word.len());
+        (space, rest) = after_word.split_at(space_end);
+    }
+
+    wrapped.push_str(&line);
+    wrapped.push('\n');
+}
+
+/// Hard-wrap the long lines of text in a gemtext document for clients which don't wrap lines.
+///
+/// Text lines and quotes longer than `width` columns are broken between words, and each line of a
+/// wrapped quote starts with `>`. Words longer than `width` are never broken, and lines which fit
+/// are left exactly as they are. Links, headings, list items, and preformatted text are left
+/// alone. Wide characters, like CJK characters and emoji, count as two columns.
+pub fn wrap_lines(body: &str, width: usize) -> String {
+    let mut wrapped = String::with_capacity(body.len());
+    let mut preformatted = false;
+
+    for line in body.lines() {
+        if line.starts_with(PREFORMAT_TOGGLE) {
+            preformatted = !preformatted;
+        }
+
+        if preformatted || line.starts_with(PREFORMAT_TOGGLE) || display_len(line) <= width {
+            wrapped.push_str(line);
+            wrapped.push('\n');
+            continue;
+        }
+
+        match parse_line(line) {
+            Line::Text(text) => {
+                // Indented lines stay indented, which also keeps them from starting with markup.
+                let indent = &text[..text.len() - text.trim_start().len()];
+                wrap_text(&mut wrapped, indent, text, width);
+            }
+            Line::Quote(text) => wrap_text(&mut wrapped, "> ", text, width),
+            _ => {
+                wrapped.push_str(line);
+                wrapped.push('\n');
+            }
+        }
+    }
+
+    if !body.ends_with('\n') {
+        wrapped.pop();
+    }
+
+    wrapped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_keeps_link_labels() {
+        assert_eq!(
+            to_plain_text("=> gemini://example.com/ An example"),
+            "An example (gemini://example.com/)"
+        );
+    }
+
+    #[test]
+    fn plain_text_keeps_unlabelled_link_urls() {
+        assert_eq!(
+            to_plain_text("=> gemini://example.com/"),
+            "gemini://example.com/"
+        );
+    }
+
+    #[test]
+    fn plain_text_removes_heading_markers() {
+        assert_eq!(to_plain_text("# One\n## Two\n### Three"), "One\nTwo\nThree");
+    }
+
+    #[test]
+    fn plain_text_removes_list_markers() {
+        assert_eq!(to_plain_text("* First\n* Second"), "First\nSecond");
+    }
+
+    #[test]
+    fn plain_text_removes_quote_markers() {
+        assert_eq!(to_plain_text("> A quote"), "A quote");
+    }
+
+    #[test]
+    fn plain_text_keeps_preformatted_text_without_toggles() {
+        assert_eq!(
+            to_plain_text("Before\n```alt text\n# not a heading\n=> not a link\n```\nAfter"),
+            "Before\n# not a heading\n=> not a link\nAfter"
+        );
+    }
+
+    #[test]
+    fn wrapping_leaves_lines_which_fit_alone() {
+        assert_eq!(wrap_lines("ab  cd", 10), "ab  cd");
+        assert_eq!(wrap_lines("ab\tcd  \n", 10), "ab\tcd  \n");
+    }
+
+    #[test]
+    fn wrapping_breaks_text_between_words() {
+        assert_eq!(wrap_lines("one two  three four", 10), "one two\nthree four");
+        assert_eq!(
+            wrap_lines("  indented text which wraps", 12),
+            "  indented\n  text which\n  wraps"
+        );
+    }
+
+    #[test]
+    fn wrapping_quotes_keeps_the_marker() {
+        assert_eq!(
+            wrap_lines("> a quote which wraps", 10),
+            "> a quote\n> which\n> wraps"
+        );
+    }
+
+    #[test]
+    fn wrapping_leaves_other_lines_alone() {
+        let body = "=> gemini://example.com/a/very/long/link A link with a long label\n\
+                    # A heading which is much too long\n\
+                    * A list item which is much too long\n\
+                    ```\n\
+                    preformatted text which is much too long\n\
+                    ```\n";
+
+        assert_eq!(wrap_lines(body, 10), body);
+    }
+
+    #[test]
+    fn wrapping_never_breaks_long_words() {
+        assert_eq!(
+            wrap_lines("a supercalifragilistic word", 10),
+            "a\nsupercalifragilistic\nword"
+        );
+    }
+
+    #[test]
+    fn wrapping_counts_multibyte_characters_by_width() {
+        // Each of these is one column, even though they take up more than one byte.
+        assert_eq!(
+            wrap_lines("caf\u{e9} cafe\u{301}", 10),
+            "caf\u{e9} cafe\u{301}"
+        );
+
+        // CJK characters take up two columns each.
+        assert_eq!(wrap_lines("日本語 日本語", 10), "日本語\n日本語");
+    }
+
+    #[test]
+    fn display_len_counts_emoji_sequences_once() {
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{200D}\u{1F466}";
+        let flag = "\u{1F1EF}\u{1F1F5}";
+        let thumbs_up = "\u{1F44D}\u{1F3FD}";
+
+        assert_eq!(display_len(family), 2);
+        assert_eq!(display_len(flag), 2);
+        assert_eq!(display_len(thumbs_up), 2);
+        assert_eq!(display_len("日本"), 4);
+        assert_eq!(display_len("e\u{301}"), 1);
+    }
+}
@@ -0,0 +1,94 @@
+use std::path::Path;
+use std::process::Command;
+
+use eyre::bail;
+
+use crate::config::PostBuildCommand;
+use crate::error::Error;
+
+/// The environment variable which holds the path of the public directory for the post-build
+/// command.
+const PUBLIC_DIR_ENV_VAR: &str = "GEMPOST_PUBLIC_DIR";
+
+/// The environment variable which holds the path of the file to sign for the signing command.
+const SIGN_FILE_ENV_VAR: &str = "GEMPOST_SIGN_FILE";
+
+impl PostBuildCommand {
+    fn to_command(&self) -> Command {
+        match self {
+            PostBuildCommand::Shell(command_line) => {
+                let mut command = Command::new("sh");
+                command.arg("-c").arg(command_line);
+                command
+            }
+            PostBuildCommand::Args(args) => {
+                // The config is validated to have at least one argument.
+                let mut command = Command::new(&args[0]);
+                command.args(&args[1..]);
+                command
+            }
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            PostBuildCommand::Shell(command_line) => command_line.clone(),
+            PostBuildCommand::Args(args) => args.join(" "),
+        }
+    }
+}
+
+// Run a command, waiting for it to finish. If it fails, this returns why.
+fn run(mut command: Command) -> Result<(), String> {
+    match command.status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => match status.code() {
+            Some(code) => Err(format!("It exited with status {code}.")),
+            None => Err(String::from("It was terminated by a signal.")),
+        },
+        Err(err) => Err(format!("It could not be started: {err}")),
+    }
+}
+
+/// Run the post-build command, waiting for it to finish.
+///
+/// The command inherits gempost's working directory and standard streams, and it's passed the
+/// path of the public directory in an environment variable.
+pub fn run_post_build(command: &PostBuildCommand, public_dir: &Path) -> eyre::Result<()> {
+    let mut process = command.to_command();
+    process.env(PUBLIC_DIR_ENV_VAR, public_dir);
+
+    if let Err(reason) = run(process) {
+        bail!(Error::PostBuildFailed {
+            command: command.describe(),
+            reason,
+        });
+    }
+
+    Ok(())
+}
+
+/// Run the signing command for a generated file, waiting for it to finish.
+///
+/// This works like the post-build command, except the path of the file to sign is in an
+/// environment variable too.
+pub fn run_sign_command(
+    command: &PostBuildCommand,
+    public_dir: &Path,
+    path: &Path,
+) -> eyre::Result<()> {
+    let mut process = command.to_command();
+    process
+        .env(PUBLIC_DIR_ENV_VAR, public_dir)
+        .env(SIGN_FILE_ENV_VAR, path);
+
+    if let Err(reason) = run(process) {
+        bail!(Error::SignCommandFailed {
+            path: path.to_owned(),
+            command: command.describe(),
+            reason,
+        });
+    }
+
+    Ok(())
+}
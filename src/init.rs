@@ -50,7 +50,7 @@ const POST_METADATA_FILE: &str = include_str!(r"examples\metadata.yaml.tera");
 #[cfg(not(windows))]
 const POST_METADATA_FILE: &str = include_str!(r"examples/metadata.yaml.tera");
 
-fn put_file(file: &Path, contents: &str) -> eyre::Result<()> {
+fn put_file(file: &Path, contents: &str, overwrite: bool) -> eyre::Result<()> {
     match file.parent() {
         None => {
             bail!("failed creating file's parent directory because it has no parent path (this is a bug)")
@@ -60,7 +60,15 @@ fn put_file(file: &Path, contents: &str) -> eyre::Result<()> {
         }
     };
 
-    let mut example_file = match OpenOptions::new().write(true).create_new(true).open(file) {
+    let mut options = OpenOptions::new();
+
+    if overwrite {
+        options.write(true).create(true).truncate(true);
+    } else {
+        options.write(true).create_new(true);
+    }
+
+    let mut example_file = match options.open(file) {
         Ok(file) => file,
         Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
             bail!(Error::ExampleFileAlreadyExists {
@@ -94,18 +102,35 @@ fn generate_example_metadata_file(template: &str) -> eyre::Result<String> {
         .wrap_err("Failed to render example metadata file template. This is a bug.")
 }
 
-pub fn init_project(dir: &Path) -> eyre::Result<()> {
-    put_file(&dir.join("gempost.yaml"), CONFIG_FILE)?;
-    put_file(&dir.join("static").join("index.gmi"), CAPSULE_INDEX_FILE)?;
+/// Generate a new project in `dir`.
+///
+/// If `force` is true, this overwrites any of the example files which already exist.
+pub fn init_project(dir: &Path, force: bool) -> eyre::Result<()> {
+    put_file(&dir.join("gempost.yaml"), CONFIG_FILE, force)?;
+    put_file(
+        &dir.join("static").join("index.gmi"),
+        CAPSULE_INDEX_FILE,
+        force,
+    )?;
     put_file(
         &dir.join("templates").join("index.tera"),
         INDEX_TEMPLATE_FILE,
+        force,
+    )?;
+    put_file(
+        &dir.join("templates").join("post.tera"),
+        POST_TEMPLATE_FILE,
+        force,
+    )?;
+    put_file(
+        &dir.join("posts").join("hello-world.gmi"),
+        GEMLOG_POST_FILE,
+        force,
     )?;
-    put_file(&dir.join("templates").join("post.tera"), POST_TEMPLATE_FILE)?;
-    put_file(&dir.join("posts").join("hello-world.gmi"), GEMLOG_POST_FILE)?;
     put_file(
         &dir.join("posts").join("hello-world.yaml"),
         &generate_example_metadata_file(POST_METADATA_FILE)?,
+        force,
     )?;
 
     Ok(())
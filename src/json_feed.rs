@@ -0,0 +1,106 @@
+use serde::Serialize;
+
+use crate::template::{EntryTemplateData, FeedTemplateData};
+
+const JSON_FEED_VERSION: &str = "https://jsonfeed.org/version/1.1";
+
+/// An author in a JSON Feed.
+///
+/// https://www.jsonfeed.org/version/1.1/#authors
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct JsonFeedAuthor<'a> {
+    pub name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<&'a str>,
+}
+
+/// A post in a JSON Feed.
+///
+/// https://www.jsonfeed.org/version/1.1/#items-a-name-items-a
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct JsonFeedItem<'a> {
+    pub id: &'a str,
+    pub url: &'a str,
+    pub title: &'a str,
+    /// The gemtext body of the post. JSON Feed has no gemtext content type, so this is plain text.
+    pub content_text: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date_published: Option<&'a str>,
+    pub date_modified: &'a str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<JsonFeedAuthor<'a>>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    pub tags: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<&'a str>,
+}
+
+impl<'a> From<&'a EntryTemplateData> for JsonFeedItem<'a> {
+    fn from(entry: &'a EntryTemplateData) -> Self {
+        Self {
+            id: &entry.id,
+            url: &entry.url,
+            title: &entry.title,
+            content_text: &entry.body,
+            summary: entry.summary.as_deref(),
+            date_published: entry.published.as_deref(),
+            date_modified: &entry.updated,
+            authors: entry
+                .authors
+                .iter()
+                .map(|author| JsonFeedAuthor {
+                    name: &author.name,
+                    url: author.uri.as_deref(),
+                })
+                .collect(),
+            tags: &entry.categories,
+            language: entry.lang.as_deref(),
+        }
+    }
+}
+
+/// A JSON Feed, which we build from the typed feed data rather than a template.
+///
+/// https://www.jsonfeed.org/version/1.1/
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct JsonFeed<'a> {
+    pub version: &'static str,
+    pub title: &'a str,
+    pub home_page_url: &'a str,
+    pub feed_url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<&'a str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<JsonFeedAuthor<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<&'a str>,
+    pub items: Vec<JsonFeedItem<'a>>,
+}
+
+impl<'a> JsonFeed<'a> {
+    pub fn new(
+        feed: &'a FeedTemplateData,
+        feed_url: &'a str,
+        entries: impl IntoIterator<Item = &'a EntryTemplateData>,
+    ) -> Self {
+        Self {
+            version: JSON_FEED_VERSION,
+            title: &feed.title,
+            home_page_url: &feed.capsule_url,
+            feed_url,
+            description: feed.subtitle.as_deref(),
+            authors: feed
+                .author
+                .iter()
+                .map(|author| JsonFeedAuthor {
+                    name: &author.name,
+                    url: author.uri.as_deref(),
+                })
+                .collect(),
+            language: feed.lang.as_deref(),
+            items: entries.into_iter().map(JsonFeedItem::from).collect(),
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use eyre::WrapErr;
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::feed::join_capsule_url;
+use crate::gemtext;
+
+// Gemini servers generally serve this file when a directory is requested.
+const DIRECTORY_INDEX_NAME: &str = "index";
+
+/// A link in a generated page which points to a page in the capsule that doesn't exist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+    /// The generated page containing the link.
+    pub source: PathBuf,
+
+    /// The line number of the link, starting at 1.
+    pub line: usize,
+
+    pub url: String,
+}
+
+// Recursively find every gemtext file in a directory.
+fn find_gemtext_files(dir: &Path, gemtext_ext: &str, files: &mut Vec<PathBuf>) -> eyre::Result<()> {
+    let mut paths = fs::read_dir(dir)
+        .wrap_err("failed reading directory contents")?
+        .map(|entry_result| entry_result.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .wrap_err("failed reading directory entry")?;
+
+    // Sort the files so broken links are always reported in the same order.
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            find_gemtext_files(&path, gemtext_ext, files)?;
+        } else if path.extension().is_some_and(|ext| ext == gemtext_ext) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+// Get the path of the file in the public directory that a URL points to. This returns `None` if
+// the URL is not within the capsule, in which case we can't check it.
+fn local_path(public_dir: &Path, capsule_url: &Url, url: &Url) -> Option<PathBuf> {
+    if url.scheme() != capsule_url.scheme()
+        || url.host_str() != capsule_url.host_str()
+        || url.port() != capsule_url.port()
+    {
+        return None;
+    }
+
+    let capsule_path = capsule_url.path().trim_end_matches('/');
+    let relative_path = url.path().strip_prefix(capsule_path)?;
+
+    // Make sure we didn't just match a prefix of a path segment.
+    if !relative_path.is_empty() && !relative_path.starts_with('/') {
+        return None;
+    }
+
+    let mut path = public_dir.to_owned();
+
+    for segment in relative_path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+    {
+        let segment = percent_decode_str(segment).decode_utf8_lossy();
+
+        // An encoded slash or dot segment would lead somewhere other than the file the server
+        // would look for, possibly outside the public directory.
+        if segment.contains(['/', '\\']) || segment == "." || segment == ".." {
+            return None;
+        }
+
+        path.push(segment.as_ref());
+    }
+
+    Some(path)
+}
+
+fn target_exists(path: &Path, gemtext_ext: &str) -> bool {
+    path.is_file()
+        || (path.is_dir()
+            && path
+                .join(format!("{DIRECTORY_INDEX_NAME}.{gemtext_ext}"))
+                .is_file())
+}
+
+/// Find every link in the generated gemtext pages which points to a page in the capsule that
+/// doesn't exist.
+///
+/// Links to other hosts or other URL schemes are not checked.
+pub fn find_broken_links(
+    public_dir: &Path,
+    capsule_url: &Url,
+    gemtext_ext: &str,
+) -> eyre::Result<Vec<BrokenLink>> {
+    let mut files = Vec::new();
+    find_gemtext_files(public_dir, gemtext_ext, &mut files)
+        .wrap_err("failed finding generated pages")?;
+    files.sort();
+
+    let mut broken_links = Vec::new();
+
+    for file in files {
+        let relative_path = file
+            .strip_prefix(public_dir)
+            .wrap_err("generated page is not in the public directory")?;
+
+        let page_path = relative_path
+            .iter()
+            .map(|segment| segment.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        // If we can't work out the URL of the page, we can't resolve the links in it either.
+        let page_url = match join_capsule_url(capsule_url, &page_path) {
+            Ok(url) => url,
+            Err(_) => continue,
+        };
+
+        let contents = fs::read_to_string(&file).wrap_err(format!(
+            "failed reading generated page: {}",
+            file.to_string_lossy()
+        ))?;
+
+        for (index, line) in gemtext::parse(&contents).into_iter().enumerate() {
+            let url = match line {
+                gemtext::Line::Link { url, .. } => url,
+                _ => continue,
+            };
+
+            let is_broken = match page_url.join(url) {
+                Ok(target_url) => match local_path(public_dir, capsule_url, &target_url) {
+                    Some(target_path) => !target_exists(&target_path, gemtext_ext),
+                    None => false,
+                },
+                Err(_) => true,
+            };
+
+            if is_broken {
+                broken_links.push(BrokenLink {
+                    source: file.clone(),
+                    line: index + 1,
+                    url: url.to_owned(),
+                });
+            }
+        }
+    }
+
+    Ok(broken_links)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(url: &str) -> Url {
+        Url::parse(url).unwrap()
+    }
+
+    #[test]
+    fn local_path_in_capsule_at_root() {
+        let public_dir = Path::new("public");
+        let capsule_url = url("gemini://example.com/");
+
+        assert_eq!(
+            local_path(
+                public_dir,
+                &capsule_url,
+                &url("gemini://example.com/posts/a.gmi")
+            ),
+            Some(PathBuf::from("public/posts/a.gmi"))
+        );
+        assert_eq!(
+            local_path(public_dir, &capsule_url, &url("gemini://example.com/")),
+            Some(PathBuf::from("public"))
+        );
+    }
+
+    #[test]
+    fn local_path_in_capsule_at_subpath() {
+        let public_dir = Path::new("public");
+
+        for capsule_url in ["gemini://example.com/blog", "gemini://example.com/blog/"] {
+            let capsule_url = url(capsule_url);
+
+            assert_eq!(
+                local_path(
+                    public_dir,
+                    &capsule_url,
+                    &url("gemini://example.com/blog/a.gmi")
+                ),
+                Some(PathBuf::from("public/a.gmi"))
+            );
+            assert_eq!(
+                local_path(public_dir, &capsule_url, &url("gemini://example.com/blog")),
+                Some(PathBuf::from("public"))
+            );
+            assert_eq!(
+                local_path(
+                    public_dir,
+                    &capsule_url,
+                    &url("gemini://example.com/other.gmi")
+                ),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn local_path_rejects_prefix_of_segment() {
+        let capsule_url = url("gemini://example.com/blog/");
+
+        assert_eq!(
+            local_path(
+                Path::new("public"),
+                &capsule_url,
+                &url("gemini://example.com/blogger/a.gmi")
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn local_path_decodes_segments() {
+        let public_dir = Path::new("public");
+        let capsule_url = url("gemini://example.com/");
+
+        assert_eq!(
+            local_path(
+                public_dir,
+                &capsule_url,
+                &url("gemini://example.com/caf%C3%A9/my%20post.gmi")
+            ),
+            Some(PathBuf::from("public/caf\u{e9}/my post.gmi"))
+        );
+
+        // An encoded slash would otherwise replace or escape the public directory.
+        for encoded in ["a%2Fb.gmi", "%2Fetc/passwd", "a%5C..%5Cb.gmi"] {
+            let link = url(&format!("gemini://example.com/{encoded}"));
+
+            assert_eq!(
+                local_path(public_dir, &capsule_url, &link),
+                None,
+                "{encoded}"
+            );
+        }
+    }
+
+    #[test]
+    fn local_path_skips_other_hosts_and_schemes() {
+        let public_dir = Path::new("public");
+        let capsule_url = url("gemini://example.com/");
+
+        for link in [
+            "gemini://other.example.com/a.gmi",
+            "gemini://example.com:1966/a.gmi",
+            "https://example.com/a.gmi",
+            "mailto:me@example.com",
+        ] {
+            assert_eq!(
+                local_path(public_dir, &capsule_url, &url(link)),
+                None,
+                "{link}"
+            );
+        }
+    }
+
+    #[test]
+    fn finds_broken_links() {
+        let public_dir = std::env::temp_dir().join(format!("gempost-links-{}", std::process::id()));
+        let capsule_url = url("gemini://example.com/~user/");
+
+        let pages = [
+            (
+                "index.gmi",
+                "=> posts/ Posts\n=> missing.gmi Missing\n=> gemini://other.example.com/ Other\n",
+            ),
+            (
+                "posts/index.gmi",
+                "=> ../ Home\n=> my%20post.gmi Post\n=> /~user/drafts/ Drafts\n",
+            ),
+            (
+                "posts/my post.gmi",
+                "Text\n=> https://example.com/missing Web\n=> /~username/ Neighbour\n",
+            ),
+            ("drafts/notes.gmi", "=> /~user/ Home\n"),
+        ];
+
+        for (path, contents) in pages {
+            let path = public_dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+
+        let broken_links = find_broken_links(&public_dir, &capsule_url, "gmi");
+        let expected = vec![
+            BrokenLink {
+                source: public_dir.join("index.gmi"),
+                line: 2,
+                url: String::from("missing.gmi"),
+            },
+            BrokenLink {
+                source: public_dir.join("posts/index.gmi"),
+                line: 3,
+                url: String::from("/~user/drafts/"),
+            },
+        ];
+
+        fs::remove_dir_all(&public_dir).unwrap();
+
+        assert_eq!(broken_links.unwrap(), expected);
+    }
+}
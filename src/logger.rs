@@ -0,0 +1,70 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// How much gempost prints while it works.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verbosity {
+    /// Only print errors.
+    Quiet,
+
+    /// Print warnings and a summary of what was done.
+    Normal,
+
+    /// Also print every file as it's generated.
+    Verbose,
+}
+
+impl Verbosity {
+    pub fn from_flags(quiet: bool, verbose: bool) -> Self {
+        if quiet {
+            Verbosity::Quiet
+        } else if verbose {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        }
+    }
+
+    fn level_filter(self) -> LevelFilter {
+        match self {
+            Verbosity::Quiet => LevelFilter::Error,
+            Verbosity::Normal => LevelFilter::Info,
+            Verbosity::Verbose => LevelFilter::Debug,
+        }
+    }
+}
+
+// A logger which prints our own log messages to stderr, so they don't get mixed up with the
+// output of commands like `gempost build --dry-run`.
+#[derive(Debug)]
+struct Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Our dependencies have their own logging, which is only useful for debugging them.
+        metadata.level() <= log::max_level()
+            && metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        match record.level() {
+            Level::Warn => eprintln!("Warning: {}", record.args()),
+            _ => eprintln!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Logger = Logger;
+
+/// Start printing log messages at the given verbosity.
+pub fn init(verbosity: Verbosity) {
+    // This only fails if a logger has already been set.
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(verbosity.level_filter());
+    }
+}
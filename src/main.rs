@@ -1,49 +1,137 @@
+mod assets;
 mod build;
+mod cache;
 mod cli;
 mod config;
 mod entry;
 mod error;
 mod feed;
+mod gemtext;
+mod hook;
 mod init;
+mod json_feed;
+mod links;
+mod logger;
+mod markdown;
 mod new;
+mod render;
+mod report;
+mod slug;
+mod snippet;
 mod template;
+mod validate;
+mod watch;
+mod xml;
 
 use std::path::Path;
 use std::process::ExitCode;
 
 use clap::Parser;
-use eyre::WrapErr;
+use eyre::{bail, WrapErr};
+use log::info;
 use new::create_new_post;
+use url::Url;
 
-use crate::build::build_capsule;
+use crate::build::{build_capsule, print_post_sources, BuildOptions};
 use crate::cli::Cli;
 use crate::config::Config;
 use crate::error::Error;
 use crate::init::init_project;
+use crate::logger::Verbosity;
+use crate::render::{render_post, render_with_fixture};
+use crate::validate::validate_capsule;
+use crate::watch::watch_capsule;
 
 fn run() -> eyre::Result<()> {
     let args = Cli::parse();
 
+    logger::init(Verbosity::from_flags(args.quiet, args.verbose));
+
     match args.command {
         cli::Commands::Init(init) => {
-            init_project(init.directory.as_deref().unwrap_or(Path::new(".")))
-                .wrap_err("failed initializing the project")?;
+            init_project(
+                init.directory.as_deref().unwrap_or(Path::new(".")),
+                init.force,
+            )
+            .wrap_err("failed initializing the project")?;
 
-            println!("Remember to edit the `gempost.yaml` to set your capsule's title and URL!")
+            info!("Remember to edit the `gempost.yaml` to set your capsule's title and URL!")
         }
         cli::Commands::Build(build) => {
-            let config =
-                Config::read(&build.config).wrap_err("failed reading the gempost config file")?;
+            let options = BuildOptions {
+                drafts: build.drafts,
+                ignore_expiry: build.ignore_expiry,
+                since: build.since,
+                until: build.until,
+                include_unpublished: build.include_unpublished,
+                force: build.force,
+                lenient_dates: build.lenient_dates,
+                strict: build.strict,
+                dry_run: build.dry_run,
+                check_links: build.check_links,
+                profile: build.profile,
+                output: build.output,
+                report: build.report,
+                capsule_url: build
+                    .capsule_url
+                    .map(|url| Url::parse(&url).map_err(|_| Error::InvalidCapsuleUrl { url }))
+                    .transpose()?,
+            };
+
+            if let (Some(since), Some(until)) = (build.since, build.until) {
+                if since > until {
+                    bail!(Error::InvalidDateRange { since, until });
+                }
+            }
+
+            if build.watch {
+                watch_capsule(&build.config, &options).wrap_err("failed watching for changes")?;
+            } else {
+                let mut config = Config::read(&build.config, options.profile.as_deref())
+                    .wrap_err("failed reading the gempost config file")?;
+
+                options.apply_to_config(&mut config);
+
+                if build.dump_sources {
+                    print_post_sources(&config, &options)
+                        .wrap_err("failed listing the post files")?;
+                } else {
+                    build_capsule(&config, &options).wrap_err("failed building the capsule")?;
+                }
+            }
+        }
+        cli::Commands::Validate(validate) => {
+            let config = Config::read(&validate.config, validate.profile.as_deref())
+                .wrap_err("failed reading the gempost config file")?;
 
-            build_capsule(&config).wrap_err("failed building the capsule")?;
+            validate_capsule(&config).wrap_err("failed validating the capsule")?;
         }
         cli::Commands::New(new) => {
-            let config =
-                Config::read(&new.config).wrap_err("failed reading the gempost config file")?;
+            let config = Config::read(&new.config, new.profile.as_deref())
+                .wrap_err("failed reading the gempost config file")?;
 
             create_new_post(&config.posts_dir, &new.slug, new.title.as_deref())
                 .wrap_err("failed creating new gemlog post")?;
         }
+        cli::Commands::Render(render) => {
+            let config = Config::read(&render.config, render.profile.as_deref())
+                .wrap_err("failed reading the gempost config file")?;
+
+            render_post(
+                &config,
+                &render.post,
+                render.metadata.as_deref(),
+                render.output.as_deref(),
+            )
+            .wrap_err("failed rendering the post")?;
+        }
+        cli::Commands::RenderTemplate(render_template) => {
+            let config = Config::read(&render_template.config, render_template.profile.as_deref())
+                .wrap_err("failed reading the gempost config file")?;
+
+            render_with_fixture(&config, &render_template.template, &render_template.fixture)
+                .wrap_err("failed rendering the template")?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,596 @@
+use std::collections::HashMap;
+
+const PREFORMAT_TOGGLE: &str = "```";
+
+// Gemtext only has three levels of headings, so deeper Markdown headings are flattened into the
+// last one.
+const MAX_HEADING_LEVEL: usize = 3;
+
+// A link found in the inline text of a block, which is written out as a link line after it.
+struct InlineLink {
+    url: String,
+    text: String,
+}
+
+// Find the closing bracket which matches an opening one at the start of some text, skipping any
+// nested or escaped brackets.
+fn find_closing(text: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut escaped = false;
+
+    for (index, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if c == open => depth += 1,
+            _ if c == close => {
+                depth -= 1;
+
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// Parse the destination of an inline link, like `(url "title")`, returning the URL and the length
+// of the whole destination.
+fn parse_destination(text: &str) -> Option<(String, usize)> {
+    let end = find_closing(text, '(', ')')?;
+    let inner = text[1..end].trim();
+
+    let url = match inner.strip_prefix('<') {
+        Some(rest) => rest.split_once('>')?.0,
+        None => inner.split_whitespace().next().unwrap_or_default(),
+    };
+
+    Some((url.to_owned(), end + 1))
+}
+
+// Parse a link or image which starts at the beginning of some text, returning its text, its URL,
+// and its length.
+fn parse_link<'a>(
+    text: &'a str,
+    references: &HashMap<String, String>,
+) -> Option<(&'a str, String, usize)> {
+    let label_end = find_closing(text, '[', ']')?;
+    let label = &text[1..label_end];
+    let rest = &text[label_end + 1..];
+
+    if rest.starts_with('(') {
+        let (url, destination_len) = parse_destination(rest)?;
+        return Some((label, url, label_end + 1 + destination_len));
+    }
+
+    // This is either a full reference link like `[text][ref]`, a collapsed one like `[text][]`, or
+    // a shortcut one like `[text]`.
+    let (reference, len) = match rest.strip_prefix('[') {
+        Some(after) => {
+            let reference_end = after.find(']')?;
+            let reference = &after[..reference_end];
+
+            if reference.is_empty() {
+                (label, label_end + 3)
+            } else {
+                (reference, label_end + reference_end + 3)
+            }
+        }
+        None => (label, label_end + 1),
+    };
+
+    let url = references.get(&normalize_reference(reference))?;
+
+    Some((label, url.clone(), len))
+}
+
+// Reference labels are case-insensitive and ignore runs of whitespace.
+fn normalize_reference(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+// Convert the inline Markdown in some text to plain text, collecting any links in it.
+fn render_inline(
+    text: &str,
+    references: &HashMap<String, String>,
+    links: &mut Vec<InlineLink>,
+) -> String {
+    let mut rendered = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        // Escaped punctuation is kept as it is.
+        if let Some(after) = rest.strip_prefix('\\') {
+            match after.chars().next() {
+                Some(escaped) if escaped.is_ascii_punctuation() => {
+                    rendered.push(escaped);
+                    rest = &after[escaped.len_utf8()..];
+                }
+                _ => {
+                    rendered.push('\\');
+                    rest = after;
+                }
+            }
+
+            continue;
+        }
+
+        // Code spans are kept as they are, but without the backticks.
+        if c == '`' {
+            let ticks = rest.len() - rest.trim_start_matches('`').len();
+            let after = &rest[ticks..];
+
+            if let Some(end) = after.find(&rest[..ticks]) {
+                rendered.push_str(after[..end].trim());
+                rest = &after[end + ticks..];
+            } else {
+                rendered.push_str(&rest[..ticks]);
+                rest = after;
+            }
+
+            continue;
+        }
+
+        // Autolinks, like `<gemini://example.com>`.
+        if c == '<' {
+            if let Some(end) = rest.find('>') {
+                let url = &rest[1..end];
+
+                if url.contains("://") && !url.contains(char::is_whitespace) {
+                    rendered.push_str(url);
+                    links.push(InlineLink {
+                        url: url.to_owned(),
+                        text: url.to_owned(),
+                    });
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            }
+        }
+
+        let is_image = rest.starts_with("![");
+        let link_start = if is_image { &rest[1..] } else { rest };
+
+        if link_start.starts_with('[') {
+            if let Some((label, url, len)) = parse_link(link_start, references) {
+                let label = render_inline(label, references, links);
+
+                rendered.push_str(&label);
+                links.push(InlineLink {
+                    url,
+                    text: if label.is_empty() {
+                        String::from(if is_image { "Image" } else { "Link" })
+                    } else {
+                        label
+                    },
+                });
+                rest = &link_start[len..];
+                continue;
+            }
+        }
+
+        // Strong emphasis is removed, since gemtext has no way to show it. Other emphasis is left
+        // alone, because `_` and `*` are too easily confused with ordinary text like `__init__`.
+        if rest.starts_with("**") {
+            rest = &rest[2..];
+            continue;
+        }
+
+        rendered.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    rendered
+}
+
+// Parse a link reference definition, like `[ref]: https://example.com "Title"`.
+fn parse_reference_definition(line: &str) -> Option<(String, String)> {
+    let line = line.trim_start();
+
+    if !line.starts_with('[') || line.starts_with("[^") {
+        return None;
+    }
+
+    let label_end = line.find("]:")?;
+    let label = &line[1..label_end];
+    let url = line[label_end + 2..].split_whitespace().next()?;
+    let url = url
+        .strip_prefix('<')
+        .and_then(|url| url.strip_suffix('>'))
+        .unwrap_or(url);
+
+    Some((normalize_reference(label), url.to_owned()))
+}
+
+fn parse_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.len() - line.trim_start_matches('#').len();
+
+    if level == 0 || level > 6 {
+        return None;
+    }
+
+    let text = &line[level..];
+
+    if !text.is_empty() && !text.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    // Headings can have closing hashes, which aren't part of the text.
+    let text = text.trim();
+    let text = match text.trim_end_matches('#') {
+        trimmed if trimmed.is_empty() || trimmed.ends_with(char::is_whitespace) => trimmed,
+        _ => text,
+    };
+
+    Some((level.min(MAX_HEADING_LEVEL), text.trim()))
+}
+
+// Parse a list item, returning its text.
+fn parse_list_item(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+
+    for marker in ["- ", "* ", "+ "] {
+        if let Some(text) = line.strip_prefix(marker) {
+            return Some(text);
+        }
+    }
+
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+    if digits == 0 || digits > 9 {
+        return None;
+    }
+
+    line[digits..]
+        .strip_prefix(". ")
+        .or_else(|| line[digits..].strip_prefix(") "))
+}
+
+fn is_thematic_break(line: &str) -> bool {
+    let line = line.trim();
+
+    ['-', '*', '_'].into_iter().any(|marker| {
+        line.chars().filter(|&c| c == marker).count() >= 3
+            && line.chars().all(|c| c == marker || c == ' ')
+    })
+}
+
+// The underline of a setext heading, like `===` or `---`.
+fn parse_setext_underline(line: &str) -> Option<usize> {
+    let line = line.trim();
+
+    if line.is_empty() {
+        None
+    } else if line.chars().all(|c| c == '=') {
+        Some(1)
+    } else if line.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+fn parse_fence(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+
+    for marker in ["```", "~~~"] {
+        if line.starts_with(marker) {
+            let len = line.len() - line.trim_start_matches(&marker[..1]).len();
+            return Some((&line[..len], line[len..].trim()));
+        }
+    }
+
+    None
+}
+
+// Build up the gemtext for a document, making sure blocks are separated by a blank line.
+struct Writer {
+    gemtext: String,
+    references: HashMap<String, String>,
+}
+
+impl Writer {
+    fn start_block(&mut self) {
+        if !self.gemtext.is_empty() && !self.gemtext.ends_with("\n\n") {
+            self.gemtext.push('\n');
+        }
+    }
+
+    fn push_line(&mut self, line: &str) {
+        self.gemtext.push_str(line);
+        self.gemtext.push('\n');
+    }
+
+    fn push_links(&mut self, links: Vec<InlineLink>) {
+        if links.is_empty() {
+            return;
+        }
+
+        self.start_block();
+
+        for link in links {
+            self.push_line(&format!("=> {} {}", link.url, link.text));
+        }
+    }
+
+    fn push_heading(&mut self, level: usize, text: &str) {
+        let mut links = Vec::new();
+        let text = render_inline(text, &self.references, &mut links);
+
+        self.start_block();
+        self.push_line(&format!("{} {text}", "#".repeat(level)));
+        self.push_links(links);
+    }
+
+    // Write a paragraph as a single line, followed by its links.
+    fn push_paragraph(&mut self, lines: &[&str]) {
+        if lines.is_empty() {
+            return;
+        }
+
+        let mut links = Vec::new();
+        let text = render_inline(&lines.join(" "), &self.references, &mut links);
+
+        self.start_block();
+        self.push_line(&text);
+        self.push_links(links);
+    }
+}
+
+/// Convert a Markdown document to gemtext.
+///
+/// Headings, lists, quotes, and code blocks become their gemtext equivalents, and the lines of each
+/// paragraph are joined into a single line. Links are replaced with their text, and each one gets
+/// a link line after the block it's in. Anything gemtext can't express, like emphasis, is removed,
+/// and HTML is left as it is.
+pub fn to_gemtext(markdown: &str) -> String {
+    let references = markdown
+        .lines()
+        .filter_map(parse_reference_definition)
+        .collect::<HashMap<_, _>>();
+
+    let mut writer = Writer {
+        gemtext: String::with_capacity(markdown.len()),
+        references,
+    };
+
+    let mut lines = markdown.lines().peekable();
+    let mut paragraph = Vec::new();
+
+    while let Some(line) = lines.next() {
+        let is_block_start = line.trim().is_empty()
+            || parse_heading(line.trim_start()).is_some()
+            || parse_fence(line).is_some()
+            || line.trim_start().starts_with('>')
+            || parse_list_item(line).is_some()
+            || is_thematic_break(line);
+
+        // A paragraph followed by an underline is a setext heading.
+        if !paragraph.is_empty() {
+            if let Some(level) = parse_setext_underline(line) {
+                writer.push_heading(level, &paragraph.join(" "));
+                paragraph.clear();
+                continue;
+            }
+        }
+
+        // Text which isn't the start of another block continues the current paragraph.
+        if !is_block_start {
+            if parse_reference_definition(line).is_none() {
+                paragraph.push(line.trim());
+            }
+
+            continue;
+        }
+
+        writer.push_paragraph(&paragraph);
+        paragraph.clear();
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if let Some((fence, alt)) = parse_fence(line) {
+            writer.start_block();
+            writer.push_line(&format!("{PREFORMAT_TOGGLE}{alt}"));
+
+            for code_line in lines.by_ref() {
+                if code_line.trim_start().starts_with(fence)
+                    && code_line.trim_start()[fence.len()..].trim().is_empty()
+                {
+                    break;
+                }
+
+                writer.push_line(code_line);
+            }
+
+            writer.push_line(PREFORMAT_TOGGLE);
+            continue;
+        }
+
+        if let Some((level, text)) = parse_heading(line.trim_start()) {
+            writer.push_heading(level, text);
+            continue;
+        }
+
+        if is_thematic_break(line) {
+            writer.start_block();
+            writer.push_line("---");
+            continue;
+        }
+
+        if line.trim_start().starts_with('>') {
+            let mut quote = vec![line];
+
+            while let Some(next) = lines.next_if(|next| next.trim_start().starts_with('>')) {
+                quote.push(next);
+            }
+
+            // Every paragraph in the quote becomes its own quote line.
+            let mut links = Vec::new();
+            let mut quote_paragraph = Vec::new();
+            writer.start_block();
+
+            for quote_line in quote.iter().chain([&">"]) {
+                let text = quote_line.trim_start()[1..].trim();
+
+                if !text.is_empty() {
+                    quote_paragraph.push(text);
+                } else if !quote_paragraph.is_empty() {
+                    let text =
+                        render_inline(&quote_paragraph.join(" "), &writer.references, &mut links);
+                    writer.push_line(&format!("> {text}"));
+                    quote_paragraph.clear();
+                }
+            }
+
+            writer.push_links(links);
+            continue;
+        }
+
+        if let Some(text) = parse_list_item(line) {
+            let mut items = vec![vec![text.trim()]];
+
+            // Indented lines continue the previous item, and blank lines between items don't
+            // end the list.
+            loop {
+                if let Some(next) = lines.next_if(|next| parse_list_item(next).is_some()) {
+                    items.push(vec![parse_list_item(next).unwrap_or_default().trim()]);
+                } else if let Some(next) = lines.next_if(|next| {
+                    next.starts_with(char::is_whitespace) && !next.trim().is_empty()
+                }) {
+                    if let Some(item) = items.last_mut() {
+                        item.push(next.trim());
+                    }
+                } else if lines.peek().is_some_and(|next| next.trim().is_empty()) {
+                    let mut lookahead = lines.clone();
+                    lookahead.next();
+
+                    if lookahead
+                        .peek()
+                        .is_some_and(|next| parse_list_item(next).is_some())
+                    {
+                        lines.next();
+                    } else {
+                        break;
+                    }
+                } else {
+                    break;
+                }
+            }
+
+            let mut links = Vec::new();
+            writer.start_block();
+
+            for item in items {
+                let text = render_inline(&item.join(" "), &writer.references, &mut links);
+                writer.push_line(&format!("* {text}"));
+            }
+
+            writer.push_links(links);
+            continue;
+        }
+    }
+
+    writer.push_paragraph(&paragraph);
+
+    writer.gemtext
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_atx_headings() {
+        assert_eq!(
+            to_gemtext("# One\n## Two\n### Three #\n#### Four\n###### Six"),
+            "# One\n\n## Two\n\n### Three\n\n### Four\n\n### Six\n"
+        );
+    }
+
+    #[test]
+    fn converts_setext_headings() {
+        assert_eq!(
+            to_gemtext("Title\n=====\n\nSubtitle\n---"),
+            "# Title\n\n## Subtitle\n"
+        );
+    }
+
+    #[test]
+    fn collects_links_after_their_block() {
+        let markdown =
+            "See [the docs](https://example.com/docs \"Docs\"), [full][ref], [ref][], and [ref].\n\
+                        \n\
+                        Next paragraph.\n\
+                        \n\
+                        [ref]: https://example.com/ref";
+
+        assert_eq!(
+            to_gemtext(markdown),
+            "See the docs, full, ref, and ref.\n\
+             \n\
+             => https://example.com/docs the docs\n\
+             => https://example.com/ref full\n\
+             => https://example.com/ref ref\n\
+             => https://example.com/ref ref\n\
+             \n\
+             Next paragraph.\n"
+        );
+    }
+
+    #[test]
+    fn converts_images_to_links() {
+        assert_eq!(
+            to_gemtext("![A cat](cat.png) and ![](dog.png)"),
+            "A cat and \n\n=> cat.png A cat\n=> dog.png Image\n"
+        );
+    }
+
+    #[test]
+    fn converts_fenced_code() {
+        assert_eq!(
+            to_gemtext("```rust\nfn main() {}\n```\n\n~~~\n# not a heading\n~~~"),
+            "```rust\nfn main() {}\n```\n\n```\n# not a heading\n```\n"
+        );
+    }
+
+    #[test]
+    fn converts_lists() {
+        assert_eq!(
+            to_gemtext("- one\n- two\n  continued\n\n- three\n\n1. first\n2) [second](b.gmi)"),
+            "* one\n* two continued\n* three\n* first\n* second\n\n=> b.gmi second\n"
+        );
+    }
+
+    #[test]
+    fn converts_quotes() {
+        assert_eq!(
+            to_gemtext("> quoted\n> with [a link](a.gmi)"),
+            "> quoted with a link\n\n=> a.gmi a link\n"
+        );
+    }
+
+    #[test]
+    fn converts_thematic_breaks() {
+        assert_eq!(
+            to_gemtext("Above\n\n***\n\nBelow"),
+            "Above\n\n---\n\nBelow\n"
+        );
+    }
+
+    #[test]
+    fn keeps_escapes_and_code_spans() {
+        assert_eq!(
+            to_gemtext("\\*not emphasis\\* and `code [x](y)` and **strong**"),
+            "*not emphasis* and code [x](y) and strong\n"
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use eyre::{bail, eyre, WrapErr};
+use log::warn;
+
+use crate::build::{add_feed_link, tidy_gemtext};
+use crate::config::Config;
+use crate::entry::{read_text_file, METADATA_FILE_EXT, POST_FILE_EXT};
+use crate::error::Error;
+use crate::feed::{Feed, FeedOptions};
+use crate::template::FeedTemplateData;
+
+/// The post path which means to read the post from stdin.
+pub const STDIN_PATH: &str = "-";
+
+// The name we give a post from stdin which has no metadata file, which is its slug unless its front
+// matter sets one.
+const STDIN_POST_NAME: &str = "stdin";
+
+/// Render the page for a single post, which doesn't need to be in the posts directory.
+///
+/// The post is read from stdin if its path is `-`. Its metadata comes from the metadata file, if
+/// one is given, or else the YAML file next to the post, or else the post's front matter. The page
+/// is written to `output`, or to stdout if that isn't set.
+pub fn render_post(
+    config: &Config,
+    post_path: &Path,
+    metadata_path: Option<&Path>,
+    output: Option<&Path>,
+) -> eyre::Result<()> {
+    let from_stdin = post_path == Path::new(STDIN_PATH);
+
+    if !from_stdin && !post_path.is_file() {
+        bail!(Error::NonexistentPostFile {
+            path: post_path.to_owned()
+        });
+    }
+
+    let metadata_path = match metadata_path {
+        Some(metadata_path) if !metadata_path.is_file() => {
+            bail!(Error::NonexistentMetadataFile {
+                path: metadata_path.to_owned()
+            });
+        }
+        Some(metadata_path) => Some(metadata_path.to_owned()),
+        None if from_stdin => None,
+        None => Some(post_path.with_extension(METADATA_FILE_EXT)).filter(|path| path.is_file()),
+    };
+
+    let (post_path, post_contents) = if from_stdin {
+        let mut post_contents = String::new();
+
+        io::stdin()
+            .read_to_string(&mut post_contents)
+            .wrap_err("failed reading post from stdin")?;
+
+        // The post doesn't have a filename to get the slug from, so we use the metadata file's.
+        let post_path = match &metadata_path {
+            Some(metadata_path) => metadata_path.with_extension(POST_FILE_EXT),
+            None => PathBuf::from(STDIN_POST_NAME).with_extension(POST_FILE_EXT),
+        };
+
+        (post_path, post_contents)
+    } else {
+        let post_contents = read_text_file(post_path).wrap_err("failed reading post body")?;
+
+        (post_path.to_owned(), post_contents)
+    };
+
+    let contents = render_entry(
+        config,
+        &post_path,
+        post_contents,
+        metadata_path.as_deref(),
+        None,
+    )?;
+
+    match output {
+        Some(output) => {
+            if let Some(parent) = output.parent() {
+                fs::create_dir_all(parent).wrap_err("failed creating output directory")?;
+            }
+
+            fs::write(output, contents).wrap_err(eyre!(
+                "failed writing post page: {}",
+                output.to_string_lossy()
+            ))
+        }
+        None => io::stdout()
+            .write_all(contents.as_bytes())
+            .wrap_err("failed writing post page to stdout"),
+    }
+}
+
+/// Render a template with the data for one fixture post, without reading the posts directory.
+///
+/// The fixture is a metadata file, just like the ones in the posts directory. The body of the post
+/// comes from the gemtext file next to it, if there is one, or else it's empty. The page is written
+/// to stdout.
+pub fn render_with_fixture(config: &Config, template: &Path, fixture: &Path) -> eyre::Result<()> {
+    if !fixture.is_file() {
+        bail!(Error::NonexistentMetadataFile {
+            path: fixture.to_owned()
+        });
+    }
+
+    let post_path = fixture.with_extension(POST_FILE_EXT);
+
+    let post_contents = if post_path.is_file() {
+        read_text_file(&post_path).wrap_err("failed reading fixture post body")?
+    } else {
+        String::new()
+    };
+
+    let contents = render_entry(
+        config,
+        &post_path,
+        post_contents,
+        Some(fixture),
+        Some(template),
+    )?;
+
+    io::stdout()
+        .write_all(contents.as_bytes())
+        .wrap_err("failed writing rendered template to stdout")
+}
+
+// Render the page for a post which is the only one in the feed. This uses the given template, or
+// else the one the post would normally use.
+fn render_entry(
+    config: &Config,
+    post_path: &Path,
+    post_contents: String,
+    metadata_path: Option<&Path>,
+    template: Option<&Path>,
+) -> eyre::Result<String> {
+    // The user asked for this post specifically, so we render it even if it's a draft or expired.
+    let feed_options = FeedOptions {
+        include_drafts: true,
+        ignore_expiry: true,
+        ..FeedOptions::default()
+    };
+
+    let feed = Feed::from_post(
+        config,
+        feed_options,
+        post_path,
+        post_contents,
+        metadata_path,
+        |msg| warn!("{}", msg),
+    )
+    .wrap_err("failed reading the post")?;
+
+    let feed_data = FeedTemplateData::from(feed.clone());
+    let feed_data = if config.obfuscate_emails {
+        feed_data.with_obfuscated_emails()
+    } else {
+        feed_data
+    };
+
+    let (entry, entry_data) = match feed
+        .entries
+        .iter()
+        .zip(&feed_data.entries)
+        .chain(
+            feed.unlisted_entries
+                .iter()
+                .zip(&feed_data.unlisted_entries),
+        )
+        .next()
+    {
+        Some(page) => page,
+        None => bail!(Error::RedirectedPost {
+            path: post_path.to_owned()
+        }),
+    };
+
+    // A template in the metadata file takes precedence over the one in the config.
+    let template = template
+        .or(entry.metadata.template.as_deref())
+        .unwrap_or(&config.post_template_file);
+
+    let contents = entry_data
+        .render(&feed_data, None, None, template, &entry.path)
+        .wrap_err("failed rendering the post")?;
+
+    let contents = add_feed_link(config, contents, &feed_data.feed_url);
+
+    Ok(tidy_gemtext(config, contents))
+}
@@ -0,0 +1,54 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use eyre::WrapErr;
+use serde::Serialize;
+
+/// A file generated by a build.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReportFile {
+    /// The path of the file, relative to the public directory.
+    pub path: PathBuf,
+
+    /// The size of the file in bytes.
+    pub size: u64,
+}
+
+/// A machine-readable summary of a build, for use by deploy tooling.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BuildReport {
+    /// Whether this was a dry run, in which case no files were actually written.
+    pub dry_run: bool,
+
+    /// The number of posts in the feed.
+    pub entries: usize,
+
+    /// The number of draft posts which were left out of the build.
+    pub skipped_drafts: usize,
+
+    /// The number of expired posts which were left out of the build.
+    pub skipped_expired: usize,
+
+    /// The number of posts which were left out of the build because of `--since` or `--until`.
+    pub skipped_out_of_range: usize,
+
+    /// When any post in the feed was last updated.
+    pub updated: String,
+
+    /// Every file in the public directory, sorted by path.
+    pub files: Vec<ReportFile>,
+
+    /// Any warnings emitted during the build.
+    pub warnings: Vec<String>,
+}
+
+impl BuildReport {
+    pub fn write(&self, path: &Path) -> eyre::Result<()> {
+        let contents =
+            serde_json::to_string_pretty(self).wrap_err("failed serializing build report")?;
+
+        fs::write(path, contents).wrap_err("failed writing build report file")?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+// Letters which do not decompose into an ASCII letter plus combining marks, and so need to be
+// transliterated explicitly.
+fn transliterate(char: char) -> Option<&'static str> {
+    Some(match char {
+        'ß' => "ss",
+        'Æ' | 'æ' => "ae",
+        'Œ' | 'œ' => "oe",
+        'Ø' | 'ø' => "o",
+        'Ł' | 'ł' => "l",
+        'Đ' | 'đ' | 'Ð' | 'ð' => "d",
+        'Þ' | 'þ' => "th",
+        'ı' => "i",
+        _ => return None,
+    })
+}
+
+/// Convert a string into a lowercase, hyphen-separated slug suitable for use in a URL path.
+///
+/// Accented Latin characters are transliterated to ASCII (e.g. é → e). Other characters which
+/// cannot be transliterated are dropped. Runs of characters which are not ASCII alphanumeric are
+/// replaced with a single hyphen, and leading and trailing hyphens are removed.
+pub fn slugify(value: &str) -> String {
+    let mut slug = String::with_capacity(value.len());
+
+    let mut push_char = |char: char| {
+        if char.is_ascii_alphanumeric() {
+            slug.push(char.to_ascii_lowercase());
+        } else if !char.is_alphanumeric() && !slug.is_empty() && !slug.ends_with('-') {
+            // Letters and digits we can't transliterate are dropped rather than replaced.
+            slug.push('-');
+        }
+    };
+
+    // Compatibility decomposition splits accented characters into their base character followed by
+    // combining marks, which we can then discard.
+    for char in value.nfkd().filter(|char| !is_combining_mark(*char)) {
+        match transliterate(char) {
+            Some(replacement) => replacement.chars().for_each(&mut push_char),
+            None => push_char(char),
+        }
+    }
+
+    if slug.ends_with('-') {
+        slug.pop();
+    }
+
+    slug
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transliterates_unicode() {
+        assert_eq!(slugify("Crème Brûlée"), "creme-brulee");
+        assert_eq!(slugify("Straße in Łódź"), "strasse-in-lodz");
+        assert_eq!(slugify("日本語 post"), "post");
+    }
+
+    #[test]
+    fn collapses_punctuation() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  --What's new?--  "), "what-s-new");
+        assert_eq!(slugify("C++ & Rust: 2024"), "c-rust-2024");
+    }
+
+    #[test]
+    fn leaves_slugs_unchanged() {
+        assert_eq!(slugify("already-a-slug"), "already-a-slug");
+        assert_eq!(slugify("post-2"), "post-2");
+    }
+}
@@ -0,0 +1,250 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+const PREFORMAT_TOGGLE: &str = "```";
+
+/// The directive which includes a snippet in a gemtext document.
+///
+/// It goes at the start of its own line, followed by the path of the snippet.
+pub const INCLUDE_DIRECTIVE: &str = "@include";
+
+// Parse an include line, returning the path of the snippet it includes.
+fn parse_include(line: &str) -> Option<&str> {
+    let path = line.strip_prefix(INCLUDE_DIRECTIVE)?;
+
+    // This has to be a separate word, not something like `@included`.
+    if !path.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    Some(path.trim())
+}
+
+// Find a snippet in the snippets directory, making sure the path doesn't lead out of it.
+fn resolve_snippet(snippets_dir: &Path, snippet: &str) -> Result<PathBuf, String> {
+    let snippet_path = Path::new(snippet);
+
+    if snippet.is_empty() {
+        return Err(format!("There is an `{INCLUDE_DIRECTIVE}` without a path."));
+    }
+
+    let mut resolved = snippets_dir.to_owned();
+
+    for component in snippet_path.components() {
+        match component {
+            Component::Normal(name) => resolved.push(name),
+            Component::CurDir => {}
+            _ => {
+                return Err(format!(
+                    "The snippet `{snippet}` must be a relative path inside the snippets directory."
+                ))
+            }
+        }
+    }
+
+    Ok(resolved)
+}
+
+fn expand(
+    body: &str,
+    snippets_dir: &Path,
+    including: &mut Vec<(PathBuf, String)>,
+    expanded: &mut String,
+) -> Result<(), String> {
+    let mut preformatted = false;
+
+    for line in body.lines() {
+        if line.starts_with(PREFORMAT_TOGGLE) {
+            preformatted = !preformatted;
+        }
+
+        let snippet = match parse_include(line) {
+            Some(snippet) if !preformatted => snippet,
+            _ => {
+                expanded.push_str(line);
+                expanded.push('\n');
+                continue;
+            }
+        };
+
+        let snippet_path = resolve_snippet(snippets_dir, snippet)?;
+
+        if including.iter().any(|(path, _)| *path == snippet_path) {
+            let chain = including
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .chain([snippet])
+                .collect::<Vec<_>>();
+
+            return Err(format!(
+                "The snippets include each other in a loop: {}",
+                chain.join(" → ")
+            ));
+        }
+
+        let snippet_body = fs::read_to_string(&snippet_path).map_err(|err| {
+            format!(
+                "The snippet `{snippet}` could not be read from `{}`: {err}",
+                snippet_path.to_string_lossy()
+            )
+        })?;
+
+        including.push((snippet_path, snippet.to_owned()));
+        expand(&snippet_body, snippets_dir, including, expanded)?;
+        including.pop();
+    }
+
+    Ok(())
+}
+
+/// Replace every `@include` line in a gemtext document with the contents of the snippet it names.
+///
+/// Snippets are found by their path relative to the snippets directory, and they can include other
+/// snippets. Include lines in preformatted text are left alone.
+pub fn expand_includes(body: &str, snippets_dir: &Path) -> Result<String, String> {
+    if !body.contains(INCLUDE_DIRECTIVE) {
+        return Ok(body.to_owned());
+    }
+
+    let mut expanded = String::with_capacity(body.len());
+    expand(body, snippets_dir, &mut Vec::new(), &mut expanded)?;
+
+    if !body.ends_with('\n') {
+        expanded.pop();
+    }
+
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A snippets directory which is removed when it's dropped.
+    struct SnippetsDir(PathBuf);
+
+    impl SnippetsDir {
+        fn new(name: &str, snippets: &[(&str, &str)]) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("gempost-snippets-{name}-{}", std::process::id()));
+
+            for (path, contents) in snippets {
+                let path = dir.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+
+            Self(dir)
+        }
+    }
+
+    impl Drop for SnippetsDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn expands_nested_includes() {
+        let snippets = SnippetsDir::new(
+            "nested",
+            &[
+                ("footer.gmi", "---\n@include ./links/home.gmi\n"),
+                ("links/home.gmi", "=> / Home\n"),
+            ],
+        );
+
+        assert_eq!(
+            expand_includes("Body\n@include footer.gmi\n", &snippets.0),
+            Ok(String::from("Body\n---\n=> / Home\n"))
+        );
+        assert_eq!(
+            expand_includes("Body\n@include footer.gmi", &snippets.0),
+            Ok(String::from("Body\n---\n=> / Home"))
+        );
+    }
+
+    #[test]
+    fn leaves_other_lines_alone() {
+        let snippets = SnippetsDir::new("other", &[]);
+
+        assert_eq!(
+            expand_includes(
+                "@included footer.gmi\nText @include footer.gmi\n",
+                &snippets.0
+            ),
+            Ok(String::from(
+                "@included footer.gmi\nText @include footer.gmi\n"
+            ))
+        );
+    }
+
+    #[test]
+    fn rejects_paths_outside_the_snippets_directory() {
+        let snippets = SnippetsDir::new("escape", &[]);
+
+        for path in ["../secret.gmi", "links/../../secret.gmi", "/etc/passwd"] {
+            assert_eq!(
+                expand_includes(&format!("@include {path}\n"), &snippets.0),
+                Err(format!(
+                    "The snippet `{path}` must be a relative path inside the snippets directory."
+                ))
+            );
+        }
+
+        assert_eq!(
+            expand_includes("@include \n", &snippets.0),
+            Err(String::from("There is an `@include` without a path."))
+        );
+    }
+
+    #[test]
+    fn rejects_include_loops() {
+        let snippets = SnippetsDir::new(
+            "loop",
+            &[
+                ("a.gmi", "@include b.gmi\n"),
+                ("b.gmi", "@include a.gmi\n"),
+                ("self.gmi", "@include self.gmi\n"),
+            ],
+        );
+
+        assert_eq!(
+            expand_includes("@include a.gmi\n", &snippets.0),
+            Err(String::from(
+                "The snippets include each other in a loop: a.gmi → b.gmi → a.gmi"
+            ))
+        );
+        assert_eq!(
+            expand_includes("@include self.gmi\n", &snippets.0),
+            Err(String::from(
+                "The snippets include each other in a loop: self.gmi → self.gmi"
+            ))
+        );
+    }
+
+    #[test]
+    fn includes_the_same_snippet_more_than_once() {
+        let snippets = SnippetsDir::new("repeat", &[("rule.gmi", "---\n")]);
+
+        assert_eq!(
+            expand_includes("@include rule.gmi\nText\n@include rule.gmi\n", &snippets.0),
+            Ok(String::from("---\nText\n---\n"))
+        );
+    }
+
+    #[test]
+    fn skips_includes_in_preformatted_text() {
+        let snippets = SnippetsDir::new("preformatted", &[("footer.gmi", "Footer\n")]);
+
+        assert_eq!(
+            expand_includes(
+                "```gemtext\n@include footer.gmi\n```\n@include footer.gmi\n",
+                &snippets.0
+            ),
+            Ok(String::from(
+                "```gemtext\n@include footer.gmi\n```\nFooter\n"
+            ))
+        );
+    }
+}
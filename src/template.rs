@@ -1,156 +1,1550 @@
-use std::fs::{self, File};
-use std::path::Path;
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
 
-use chrono::{DateTime, Datelike, FixedOffset};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, SecondsFormat, Timelike, Utc};
 use eyre::{bail, eyre, WrapErr};
+use percent_encoding::percent_decode_str;
 use serde::{Deserialize, Serialize};
 use tera::{Context, Tera};
+use unicode_normalization::UnicodeNormalization;
+use url::Url;
+use walkdir::WalkDir;
 
-use crate::entry::{AuthorMetadata, Entry};
+use crate::cache::Fingerprint;
+use crate::config::MissingPublished;
+use crate::entry::{AuthorMetadata, EnclosureMetadata, Entry, EntryMetadata, Translation};
 use crate::error::Error;
-use crate::feed::{Feed, FeedAuthor};
+use crate::feed::{join_capsule_url, Category, Feed, FeedAuthor, IndexPage, Redirect, Subfeed};
+use crate::gemtext;
+use crate::json_feed::JsonFeed;
+use crate::slug::slugify;
 
-#[derive(Debug, PartialEq, Eq, Serialize)]
+const FEED_TEMPLATE: &str = include_str!("atom.xml.tera");
+
+// Only files with this extension are loaded from the includes directory.
+const INCLUDE_FILE_EXT: &str = "tera";
+
+// The number of hex digits to keep from the hash of each entry.
+const CHECKSUM_LEN: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct EntryAuthorTemplateData {
     pub name: String,
     pub email: Option<String>,
     pub uri: Option<String>,
 }
 
-impl From<AuthorMetadata> for EntryAuthorTemplateData {
-    fn from(value: AuthorMetadata) -> Self {
-        Self {
-            name: value.name,
-            email: value.email,
-            uri: value.uri,
-        }
+impl From<AuthorMetadata> for EntryAuthorTemplateData {
+    fn from(value: AuthorMetadata) -> Self {
+        Self {
+            name: value.name,
+            email: value.email,
+            uri: value.uri,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EnclosureTemplateData {
+    pub url: String,
+    pub mime_type: String,
+    pub length: Option<u64>,
+}
+
+impl From<EnclosureMetadata> for EnclosureTemplateData {
+    fn from(value: EnclosureMetadata) -> Self {
+        Self {
+            url: value.url,
+            mime_type: value.mime_type,
+            length: value.length,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EntryTemplateData {
+    pub id: String,
+    pub url: String,
+    pub title: String,
+    pub body: String,
+    pub checksum: String,
+    pub updated: String,
+    pub summary: Option<String>,
+    pub teaser: Option<String>,
+    pub published: Option<String>,
+    pub publish_year: Option<i32>,
+    pub is_updated: bool,
+    pub author: Option<EntryAuthorTemplateData>,
+    pub authors: Vec<EntryAuthorTemplateData>,
+    pub rights: Option<String>,
+    pub lang: Option<String>,
+    pub categories: Vec<String>,
+    pub translations: Vec<TranslationTemplateData>,
+    pub pinned: bool,
+    /// The path of the post file, relative to the posts directory.
+    pub source_path: String,
+    /// When the post file was last modified, which is independent of its `updated` time.
+    pub file_modified: Option<String>,
+    /// Whether the syndication feeds include the body of this entry, rather than just its summary.
+    pub full_content: bool,
+    pub weight: Option<i64>,
+    pub word_count: usize,
+    pub reading_time: u32,
+    pub link_count: usize,
+    pub internal_link_count: usize,
+    pub external_link_count: usize,
+    pub headings: Vec<HeadingTemplateData>,
+    pub preformatted_blocks: Vec<PreformattedBlockTemplateData>,
+    pub breadcrumbs: Vec<BreadcrumbTemplateData>,
+    pub enclosures: Vec<EnclosureTemplateData>,
+    pub feed_extra: Option<String>,
+    pub values: BTreeMap<String, serde_yaml::Value>,
+}
+
+// A short hash of an entry's body and the metadata that identifies it. The inputs are hashed in a
+// fixed order, so this is the same on every platform.
+fn entry_checksum(metadata: &EntryMetadata, body: &str) -> String {
+    let mut fingerprint = Fingerprint::new();
+    fingerprint.update(&metadata.id);
+    fingerprint.update(&metadata.title);
+    fingerprint.update(metadata.updated.to_rfc3339());
+    fingerprint.update(
+        metadata
+            .published
+            .as_ref()
+            .map(DateTime::<FixedOffset>::to_rfc3339)
+            .unwrap_or_default(),
+    );
+    fingerprint.update(body);
+
+    let mut checksum = fingerprint.finish();
+    checksum.truncate(CHECKSUM_LEN);
+    checksum
+}
+
+/// Another language version of an entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TranslationTemplateData {
+    pub lang: Option<String>,
+    pub url: String,
+    pub title: String,
+}
+
+impl From<Translation> for TranslationTemplateData {
+    fn from(translation: Translation) -> Self {
+        Self {
+            lang: translation.lang,
+            url: translation.url.to_string(),
+            title: translation.title,
+        }
+    }
+}
+
+/// A link in the breadcrumb trail from the capsule's homepage to an entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BreadcrumbTemplateData {
+    pub name: String,
+    pub url: String,
+    /// Whether this is the entry itself, which templates generally shouldn't link to.
+    pub is_current: bool,
+}
+
+// The breadcrumb trail from the capsule's homepage to an entry, with a link for each directory in
+// between. The entry itself is named by its title.
+fn breadcrumbs(
+    capsule_url: &Url,
+    entry_url: &Url,
+    title: &str,
+    home: &str,
+) -> Vec<BreadcrumbTemplateData> {
+    let capsule_depth = capsule_url.path_segments().map_or(0, |segments| {
+        segments.filter(|segment| !segment.is_empty()).count()
+    });
+
+    let segments = entry_url
+        .path_segments()
+        .map(|segments| {
+            segments
+                .filter(|segment| !segment.is_empty())
+                .skip(capsule_depth)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let mut breadcrumbs = vec![BreadcrumbTemplateData {
+        name: home.to_owned(),
+        url: capsule_url.to_string(),
+        is_current: segments.is_empty(),
+    }];
+
+    let Some((_, dirs)) = segments.split_last() else {
+        return breadcrumbs;
+    };
+
+    // Directory URLs need a trailing slash, and relative URLs are resolved against the last
+    // directory in the base URL, so the capsule URL needs one too.
+    let mut base_url = capsule_url.clone();
+
+    if !base_url.path().ends_with('/') {
+        base_url.set_path(&format!("{}/", base_url.path()));
+    }
+
+    for (index, dir) in dirs.iter().enumerate() {
+        // The segments are already percent-encoded.
+        let dir_path = format!("{}/", dirs[..=index].join("/"));
+
+        if let Ok(dir_url) = base_url.join(&dir_path) {
+            breadcrumbs.push(BreadcrumbTemplateData {
+                name: percent_decode_str(dir).decode_utf8_lossy().nfc().collect(),
+                url: dir_url.to_string(),
+                is_current: false,
+            });
+        }
+    }
+
+    breadcrumbs.push(BreadcrumbTemplateData {
+        name: title.to_owned(),
+        url: entry_url.to_string(),
+        is_current: true,
+    });
+
+    breadcrumbs
+}
+
+/// A heading in the body of an entry, for generating a table of contents.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HeadingTemplateData {
+    pub level: u8,
+    pub text: String,
+    pub anchor: String,
+}
+
+/// A preformatted block in the body of an entry, like a code listing or ASCII art.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PreformattedBlockTemplateData {
+    pub alt: Option<String>,
+    pub content: String,
+}
+
+impl From<gemtext::PreformattedBlock<'_>> for PreformattedBlockTemplateData {
+    fn from(block: gemtext::PreformattedBlock) -> Self {
+        Self {
+            alt: block.alt.map(ToOwned::to_owned),
+            content: block.content,
+        }
+    }
+}
+
+// Whether a link in an entry points somewhere in the capsule. Relative links always do, while
+// absolute links only do if they're under the capsule URL.
+fn is_internal_link(entry_url: &Url, capsule_url: &Url, link: &str) -> bool {
+    let url = match entry_url.join(link) {
+        Ok(url) => url,
+        Err(_) => return false,
+    };
+
+    url.scheme() == capsule_url.scheme()
+        && url.host_str() == capsule_url.host_str()
+        && url.port() == capsule_url.port()
+        && url.path().starts_with(capsule_url.path())
+}
+
+// Count the links in a parsed gemtext document which point somewhere in the capsule, and the ones
+// which point somewhere else, in that order.
+fn link_counts(lines: &[gemtext::Line], entry_url: &Url, capsule_url: &Url) -> (usize, usize) {
+    lines
+        .iter()
+        .filter_map(|line| match line {
+            gemtext::Line::Link { url, .. } => Some(url),
+            _ => None,
+        })
+        .fold((0, 0), |(internal, external), url| {
+            if is_internal_link(entry_url, capsule_url, url) {
+                (internal + 1, external)
+            } else {
+                (internal, external + 1)
+            }
+        })
+}
+
+// Tera only understands JSON-like values, so this strips YAML tags like `!foo`, which would
+// otherwise become a mapping with the tag as its key, and turns mapping keys which aren't strings
+// into strings. Everything else keeps its YAML type.
+fn template_value(value: serde_yaml::Value) -> serde_yaml::Value {
+    match value {
+        serde_yaml::Value::Tagged(tagged) => template_value(tagged.value),
+        serde_yaml::Value::Sequence(sequence) => {
+            serde_yaml::Value::Sequence(sequence.into_iter().map(template_value).collect())
+        }
+        serde_yaml::Value::Mapping(mapping) => serde_yaml::Value::Mapping(
+            mapping
+                .into_iter()
+                .map(|(key, value)| (template_key(key), template_value(value)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+fn template_key(key: serde_yaml::Value) -> serde_yaml::Value {
+    let key = match template_value(key) {
+        serde_yaml::Value::String(key) => key,
+        serde_yaml::Value::Null => String::from("null"),
+        serde_yaml::Value::Bool(key) => key.to_string(),
+        serde_yaml::Value::Number(key) => key.to_string(),
+        key => tera::to_value(&key)
+            .map(|key| key.to_string())
+            .unwrap_or_default(),
+    };
+
+    serde_yaml::Value::String(key)
+}
+
+// The path of a post file as templates see it. This is relative to the posts directory, and it
+// always uses forward slashes, regardless of the platform. A post from outside the posts directory,
+// like one rendered on its own, keeps the path it was read from.
+fn template_source_path(path: &Path, posts_dir: &Path) -> String {
+    // A posts directory like `./posts/` should still match a post path like `posts/foo.gmi`.
+    let without_cur_dir = |path: &Path| {
+        path.components()
+            .filter(|component| !matches!(component, Component::CurDir))
+            .collect::<PathBuf>()
+    };
+
+    let path = without_cur_dir(path);
+    let posts_dir = without_cur_dir(posts_dir);
+    let path = path.strip_prefix(&posts_dir).unwrap_or(&path);
+
+    path.components()
+        .map(|component| match component {
+            // Joining this with the components after it gives the leading slash.
+            Component::RootDir => Cow::Borrowed(""),
+            component => component.as_os_str().to_string_lossy(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Get the headings from a parsed gemtext document. Anchors are unique within the document; when two
+// headings have the same text, we add a numeric suffix to the later ones.
+fn headings(lines: &[gemtext::Line]) -> Vec<HeadingTemplateData> {
+    let mut headings = Vec::new();
+    let mut anchor_counts = HashMap::new();
+
+    for line in lines {
+        if let gemtext::Line::Heading { level, text } = line {
+            let slug = slugify(text);
+            let count = anchor_counts.entry(slug.clone()).or_insert(0);
+            *count += 1;
+
+            let anchor = if *count == 1 {
+                slug
+            } else {
+                format!("{slug}-{count}")
+            };
+
+            headings.push(HeadingTemplateData {
+                level: *level,
+                text: text.to_string(),
+                anchor,
+            });
+        }
+    }
+
+    headings
+}
+
+/// A link to another entry, such as the previous or next post.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct EntryLinkTemplateData {
+    pub url: String,
+    pub title: String,
+}
+
+impl From<&EntryTemplateData> for EntryLinkTemplateData {
+    fn from(entry: &EntryTemplateData) -> Self {
+        Self {
+            url: entry.url.clone(),
+            title: entry.title.clone(),
+        }
+    }
+}
+
+/// Settings which affect how we generate the template data for each entry.
+#[derive(Debug, Clone, Copy)]
+pub struct EntryTemplateOptions<'a> {
+    pub words_per_minute: u32,
+
+    /// The maximum length of auto-generated summaries, or zero to disable them.
+    pub summary_length: usize,
+
+    /// Whether to remove gemtext markup from summaries in metadata files.
+    pub plain_summaries: bool,
+
+    /// The line which separates the teaser from the rest of each entry.
+    pub more_marker: &'a str,
+
+    /// The language to use for entries which don't specify one.
+    pub default_lang: Option<&'a str>,
+
+    /// Whether to expand inline links for entries which don't specify whether to.
+    pub inline_links: bool,
+
+    /// The URL of the capsule's homepage, which is the root of each entry's breadcrumbs.
+    pub capsule_url: &'a Url,
+
+    /// The name of the homepage in each entry's breadcrumbs.
+    pub breadcrumb_home: &'a str,
+
+    /// The posts directory, which each entry's source path is relative to.
+    pub posts_dir: &'a Path,
+}
+
+impl EntryTemplateData {
+    pub fn from_entry(params: Entry, options: EntryTemplateOptions) -> Self {
+        let (body, teaser) = gemtext::split_teaser(&params.body, options.more_marker);
+
+        let (body, teaser) = if params.metadata.inline_links.unwrap_or(options.inline_links) {
+            (
+                gemtext::expand_inline_links(&body),
+                teaser.as_deref().map(gemtext::expand_inline_links),
+            )
+        } else {
+            (body, teaser)
+        };
+
+        let checksum = entry_checksum(&params.metadata, &body);
+        let lines = gemtext::parse(&body);
+        let word_count = gemtext::word_count(&lines);
+        let (internal_link_count, external_link_count) =
+            link_counts(&lines, &params.url, options.capsule_url);
+        let headings = headings(&lines);
+        let preformatted_blocks = gemtext::preformatted_blocks(&lines)
+            .into_iter()
+            .map(PreformattedBlockTemplateData::from)
+            .collect();
+        let breadcrumbs = breadcrumbs(
+            options.capsule_url,
+            &params.url,
+            &params.metadata.title,
+            options.breadcrumb_home,
+        );
+
+        // If the user didn't provide a summary, we generate one from the first paragraph.
+        let summary = match params.metadata.summary {
+            Some(summary) if options.plain_summaries => Some(gemtext::to_plain_text(&summary)),
+            Some(summary) => Some(summary),
+            None if options.summary_length == 0 => None,
+            None => gemtext::first_paragraph(&lines)
+                .map(|paragraph| gemtext::truncate_words(paragraph, options.summary_length)),
+        };
+
+        // Without a marker, the teaser is the summary, or the whole first paragraph if summaries
+        // are disabled.
+        let teaser = teaser.or_else(|| {
+            summary
+                .clone()
+                .or_else(|| gemtext::first_paragraph(&lines).map(ToOwned::to_owned))
+        });
+
+        let authors = params
+            .metadata
+            .authors
+            .into_iter()
+            .map(EntryAuthorTemplateData::from)
+            .collect::<Vec<_>>();
+
+        Self {
+            id: params.metadata.id,
+            url: params.url.to_string(),
+            title: params.metadata.title,
+            body,
+            checksum,
+            updated: params.metadata.updated.to_rfc3339(),
+            teaser,
+            summary,
+            published: params
+                .metadata
+                .published
+                .as_ref()
+                .map(DateTime::<FixedOffset>::to_rfc3339),
+            publish_year: params.metadata.published.as_ref().map(Datelike::year),
+            // This compares the instants, so it works even if the timestamps have different
+            // offsets.
+            is_updated: params
+                .metadata
+                .published
+                .is_some_and(|published| params.metadata.updated > published),
+            // This is just the first author, for backwards compatibility.
+            author: authors.first().cloned(),
+            authors,
+            rights: params.metadata.rights,
+            lang: params
+                .metadata
+                .lang
+                .or_else(|| options.default_lang.map(ToOwned::to_owned)),
+            categories: params.metadata.categories,
+            translations: params
+                .translations
+                .into_iter()
+                .map(TranslationTemplateData::from)
+                .collect(),
+            pinned: params.metadata.pinned,
+            source_path: template_source_path(&params.source_path, options.posts_dir),
+            file_modified: params
+                .file_modified
+                .as_ref()
+                .map(DateTime::<FixedOffset>::to_rfc3339),
+            full_content: true,
+            weight: params.metadata.weight,
+            word_count,
+            reading_time: gemtext::reading_time(word_count, options.words_per_minute),
+            link_count: internal_link_count + external_link_count,
+            internal_link_count,
+            external_link_count,
+            headings,
+            preformatted_blocks,
+            breadcrumbs,
+            enclosures: params
+                .metadata
+                .enclosures
+                .into_iter()
+                .map(EnclosureTemplateData::from)
+                .collect(),
+            feed_extra: params.metadata.feed_extra,
+            values: params
+                .metadata
+                .values
+                .into_iter()
+                .map(|(key, value)| (key, template_value(value)))
+                .collect(),
+        }
+    }
+
+    pub fn render(
+        &self,
+        feed: &FeedTemplateData,
+        prev: Option<&EntryLinkTemplateData>,
+        next: Option<&EntryLinkTemplateData>,
+        template: &Path,
+        output: &Path,
+    ) -> eyre::Result<String> {
+        let mut tera = Tera::default();
+        feed.configure_tera(&mut tera, &self.url)?;
+
+        if let Err(err) = tera.add_template_file(template, Some("post")) {
+            bail!(Error::InvalidPostPageTemplate {
+                path: output.to_owned(),
+                reason: error_reason(&err),
+            });
+        }
+
+        let mut context = feed.new_context();
+        context.insert("entry", self);
+        context.insert("feed", feed);
+
+        // These are left out of the context entirely for the oldest and newest posts.
+        if let Some(prev) = prev {
+            context.insert("prev", prev);
+        }
+
+        if let Some(next) = next {
+            context.insert("next", next);
+        }
+
+        match tera.render("post", &context) {
+            Ok(contents) => Ok(contents),
+            Err(err) => bail!(Error::InvalidPostPageTemplate {
+                path: output.to_owned(),
+                reason: render_error_reason(&err, template),
+            }),
+        }
+    }
+}
+
+/// The position of an index page within the paginated index.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct PaginationTemplateData {
+    /// The URL of the current page.
+    pub url: String,
+    pub current: usize,
+    pub total: usize,
+    pub prev_url: Option<String>,
+    pub next_url: Option<String>,
+}
+
+impl PaginationTemplateData {
+    pub fn new(pages: &[IndexPage], index: usize) -> Self {
+        Self {
+            url: pages[index].url.to_string(),
+            current: pages[index].number,
+            total: pages.len(),
+            prev_url: index
+                .checked_sub(1)
+                .and_then(|prev_index| pages.get(prev_index))
+                .map(|page| page.url.to_string()),
+            next_url: pages.get(index + 1).map(|page| page.url.to_string()),
+        }
+    }
+}
+
+impl FeedTemplateData {
+    // Register the filters and functions shared by all templates, along with the ones which need
+    // to know about every post in the capsule or the URL of the page being rendered.
+    fn configure_tera(&self, tera: &mut Tera, page_url: &str) -> eyre::Result<()> {
+        configure_tera(tera, &self.capsule_url);
+
+        // Relative times are relative to when the capsule was built, which is the same for every
+        // page.
+        let build_time = DateTime::parse_from_rfc3339(&self.site.build_time)
+            .wrap_err("failed parsing the build time")?;
+        tera.register_filter(
+            "timeago",
+            move |value: &tera::Value, _: &HashMap<String, tera::Value>| {
+                let datetime = parse_timestamp_value("timeago", value)?;
+                Ok(tera::Value::String(time_ago(&datetime, &build_time)))
+            },
+        );
+
+        let capsule_url = self.capsule_url.clone();
+        let page_url = page_url.to_owned();
+        tera.register_filter(
+            "relative_url",
+            move |value: &tera::Value, _: &HashMap<String, tera::Value>| {
+                relative_url_filter(&capsule_url, &page_url, value)
+            },
+        );
+
+        // These need to be loaded before the templates which import them.
+        if let Some(includes_dir) = &self.includes_dir {
+            load_includes(tera, includes_dir)?;
+        }
+
+        // We know the URL of every post before we render any of them, so templates can link to
+        // other posts by their ID.
+        let post_urls = self.post_urls.clone();
+
+        tera.register_function("post_url", move |args: &HashMap<String, tera::Value>| {
+            post_url_function(&post_urls, args)
+        });
+
+        Ok(())
+    }
+
+    /// The template data for a subfeed, which is this feed with only the posts in its categories.
+    ///
+    /// The subfeed only has an Atom feed, so the URLs of the other feeds are left out.
+    pub fn subfeed(&self, subfeed: &Subfeed) -> Self {
+        Self {
+            // Every feed needs its own ID, and the capsule URL is already the main feed's.
+            id: subfeed.feed_url.to_string(),
+            feed_url: subfeed.feed_url.to_string(),
+            rss_url: None,
+            gemsub_url: None,
+            json_feed_url: None,
+            index_url: subfeed.index_page.url.to_string(),
+            title: subfeed.title.clone(),
+            updated: feed_timestamp(&subfeed.updated),
+            entries: self
+                .entries
+                .iter()
+                .filter(|entry| subfeed.includes(&entry.categories))
+                .cloned()
+                .collect(),
+            updated_entries: self
+                .updated_entries
+                .iter()
+                .filter(|entry| subfeed.includes(&entry.categories))
+                .cloned()
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// A copy of this data with every author's email address obfuscated, for rendering pages.
+    pub fn with_obfuscated_emails(&self) -> Self {
+        let obfuscate = |email: &mut Option<String>| {
+            *email = email.as_deref().map(obfuscate_email);
+        };
+
+        let mut data = self.clone();
+
+        if let Some(author) = &mut data.author {
+            obfuscate(&mut author.email);
+        }
+
+        for entry in data
+            .entries
+            .iter_mut()
+            .chain(&mut data.unlisted_entries)
+            .chain(&mut data.updated_entries)
+        {
+            if let Some(author) = &mut entry.author {
+                obfuscate(&mut author.email);
+            }
+
+            for author in &mut entry.authors {
+                obfuscate(&mut author.email);
+            }
+        }
+
+        data
+    }
+
+    pub fn render_index(
+        &self,
+        entries: &[EntryTemplateData],
+        pagination: &PaginationTemplateData,
+        template: &Path,
+    ) -> eyre::Result<String> {
+        let mut tera = Tera::default();
+        self.configure_tera(&mut tera, &pagination.url)?;
+
+        if let Err(err) = tera.add_template_file(template, Some("index")) {
+            bail!(Error::InvalidIndexPageTemplate {
+                reason: error_reason(&err)
+            });
+        }
+
+        let mut context = self.new_context();
+        context.insert("feed", self);
+        context.insert("entries", entries);
+        context.insert("updated_entries", &self.updated_entries);
+        context.insert("pagination", pagination);
+
+        match tera.render("index", &context) {
+            Ok(contents) => Ok(contents),
+            Err(err) => bail!(Error::InvalidIndexPageTemplate {
+                reason: render_error_reason(&err, template),
+            }),
+        }
+    }
+
+    // The indices of the entries to include in a syndication feed, in their original order. This
+    // is at most `limit` of the most recently published entries.
+    fn syndicated_entry_indices(&self, limit: Option<usize>) -> Vec<usize> {
+        let mut indices = (0..self.entries.len())
+            .filter(|&index| {
+                self.missing_published != MissingPublished::Exclude
+                    || self.entries[index].published.is_some()
+            })
+            .collect::<Vec<_>>();
+
+        let limit = match limit {
+            Some(limit) if limit < indices.len() => limit,
+            _ => return indices,
+        };
+
+        // Entries might not be sorted by date, so we need to find the most recent ones. We
+        // generate all these timestamps ourselves, so they're always valid.
+        indices.sort_by_cached_key(|&index| {
+            let entry = &self.entries[index];
+            let timestamp = entry.published.as_ref().unwrap_or(&entry.updated);
+            cmp::Reverse(DateTime::parse_from_rfc3339(timestamp).ok())
+        });
+
+        indices.truncate(limit);
+        indices.sort();
+
+        indices
+    }
+
+    // A template context with the variables every template has access to.
+    fn new_context(&self) -> Context {
+        let mut context = Context::new();
+        context.insert("site", &self.site);
+        context
+    }
+
+    // Build the context for a syndication feed, including at most `limit` of the most recent
+    // entries. The entries keep the order they're sorted in.
+    fn syndication_context(&self, limit: Option<usize>) -> eyre::Result<Context> {
+        let mut context = self.new_context();
+
+        let indices = self.syndicated_entry_indices(limit);
+
+        // Only the most recent entries have their full content, if there's a limit.
+        let full_content_indices = self.full_content_limit.map(|full_content_limit| {
+            self.syndicated_entry_indices(Some(full_content_limit))
+                .into_iter()
+                .collect::<BTreeSet<_>>()
+        });
+
+        // If we're including every entry as it is, we don't need to modify the feed.
+        if indices.len() == self.entries.len()
+            && self.missing_published != MissingPublished::Updated
+            && full_content_indices.is_none()
+        {
+            context.insert("feed", self);
+            return Ok(context);
+        }
+
+        let mut feed = tera::to_value(self).wrap_err("failed serializing feed template data")?;
+
+        if let Some(entries) = feed.get_mut("entries").and_then(tera::Value::as_array_mut) {
+            *entries = indices
+                .into_iter()
+                .map(|index| {
+                    let mut entry = entries[index].take();
+
+                    if let Some(full_content_indices) = &full_content_indices {
+                        entry["full_content"] = full_content_indices.contains(&index).into();
+                    }
+
+                    entry
+                })
+                .collect();
+
+            if self.missing_published == MissingPublished::Updated {
+                for entry in entries.iter_mut() {
+                    if entry["published"].is_null() {
+                        entry["published"] = entry["updated"].clone();
+                    }
+                }
+            }
+        }
+
+        context.insert("feed", &feed);
+
+        Ok(context)
+    }
+
+    /// Render the Atom feed, using the bundled template unless the user provided their own.
+    pub fn render_feed(
+        &self,
+        template: Option<&Path>,
+        limit: Option<usize>,
+    ) -> eyre::Result<String> {
+        let mut tera = Tera::default();
+        self.configure_tera(&mut tera, &self.feed_url)?;
+
+        let context = self.syndication_context(limit)?;
+
+        let template = match template {
+            Some(template) => template,
+            None => {
+                tera.add_raw_template("feed", FEED_TEMPLATE)
+                    .wrap_err("The bundled Atom feed template is invalid. This is a bug.")?;
+
+                return tera
+                    .render("feed", &context)
+                    .wrap_err("failed generating the Atom feed");
+            }
+        };
+
+        if let Err(err) = tera.add_template_file(template, Some("feed")) {
+            bail!(Error::InvalidFeedTemplate {
+                reason: error_reason(&err)
+            });
+        }
+
+        match tera.render("feed", &context) {
+            Ok(contents) => Ok(contents),
+            Err(err) => bail!(Error::InvalidFeedTemplate {
+                reason: render_error_reason(&err, template),
+            }),
+        }
+    }
+
+    pub fn render_category(
+        &self,
+        category: &CategoryTemplateData,
+        template: &Path,
+    ) -> eyre::Result<String> {
+        let mut tera = Tera::default();
+        self.configure_tera(&mut tera, &category.url)?;
+
+        if let Err(err) = tera.add_template_file(template, Some("category")) {
+            bail!(Error::InvalidCategoryPageTemplate {
+                reason: error_reason(&err)
+            });
+        }
+
+        let entries = self
+            .entries
+            .iter()
+            .filter(|entry| {
+                entry
+                    .categories
+                    .iter()
+                    .any(|name| slugify(name) == category.slug)
+            })
+            .collect::<Vec<_>>();
+
+        let mut context = self.new_context();
+        context.insert("feed", self);
+        context.insert("category", &category.name);
+        context.insert("entries", &entries);
+
+        match tera.render("category", &context) {
+            Ok(contents) => Ok(contents),
+            Err(err) => bail!(Error::InvalidCategoryPageTemplate {
+                reason: render_error_reason(&err, template),
+            }),
+        }
+    }
+
+    pub fn render_categories(&self, template: &Path) -> eyre::Result<String> {
+        let mut tera = Tera::default();
+        self.configure_tera(&mut tera, &self.categories_url)?;
+
+        if let Err(err) = tera.add_template_file(template, Some("categories")) {
+            bail!(Error::InvalidCategoriesPageTemplate {
+                reason: error_reason(&err)
+            });
+        }
+
+        let mut context = self.new_context();
+        context.insert("feed", self);
+        context.insert("categories", &self.categories);
+
+        match tera.render("categories", &context) {
+            Ok(contents) => Ok(contents),
+            Err(err) => bail!(Error::InvalidCategoriesPageTemplate {
+                reason: render_error_reason(&err, template),
+            }),
+        }
+    }
+
+    pub fn render_archive(&self, template: &Path) -> eyre::Result<String> {
+        let mut tera = Tera::default();
+        self.configure_tera(&mut tera, &self.archive_url)?;
+
+        if let Err(err) = tera.add_template_file(template, Some("archive")) {
+            bail!(Error::InvalidArchivePageTemplate {
+                reason: error_reason(&err)
+            });
+        }
+
+        let mut context = self.new_context();
+        context.insert("feed", self);
+        context.insert("years", &ArchiveYearTemplateData::group(&self.entries));
+
+        match tera.render("archive", &context) {
+            Ok(contents) => Ok(contents),
+            Err(err) => bail!(Error::InvalidArchivePageTemplate {
+                reason: render_error_reason(&err, template),
+            }),
+        }
+    }
+
+    pub fn render_robots(&self, template: &Path) -> eyre::Result<String> {
+        let mut tera = Tera::default();
+        self.configure_tera(&mut tera, &self.capsule_url)?;
+
+        if let Err(err) = tera.add_template_file(template, Some("robots")) {
+            bail!(Error::InvalidRobotsTemplate {
+                reason: error_reason(&err)
+            });
+        }
+
+        let mut context = self.new_context();
+        context.insert("feed", self);
+
+        match tera.render("robots", &context) {
+            Ok(contents) => Ok(contents),
+            Err(err) => bail!(Error::InvalidRobotsTemplate {
+                reason: render_error_reason(&err, template),
+            }),
+        }
+    }
+
+    pub fn render_security(&self, template: &Path) -> eyre::Result<String> {
+        let mut tera = Tera::default();
+        self.configure_tera(&mut tera, &self.capsule_url)?;
+
+        if let Err(err) = tera.add_template_file(template, Some("security")) {
+            bail!(Error::InvalidSecurityTemplate {
+                reason: error_reason(&err)
+            });
+        }
+
+        let mut context = self.new_context();
+        context.insert("feed", self);
+
+        match tera.render("security", &context) {
+            Ok(contents) => Ok(contents),
+            Err(err) => bail!(Error::InvalidSecurityTemplate {
+                reason: render_error_reason(&err, template),
+            }),
+        }
+    }
+
+    /// Render the JSON Feed. Unlike the other feeds, this is built from the feed data directly
+    /// rather than from a template.
+    pub fn render_json_feed(&self, limit: Option<usize>) -> eyre::Result<String> {
+        let feed_url = self
+            .json_feed_url
+            .as_deref()
+            .ok_or(eyre!("There is no JSON Feed URL. This is a bug."))?;
+
+        let entries = self
+            .syndicated_entry_indices(limit)
+            .into_iter()
+            .map(|index| &self.entries[index]);
+
+        let mut json_feed = JsonFeed::new(self, feed_url, entries);
+
+        if self.missing_published == MissingPublished::Updated {
+            for item in &mut json_feed.items {
+                item.date_published = item.date_published.or(Some(item.date_modified));
+            }
+        }
+
+        serde_json::to_string_pretty(&json_feed).wrap_err("failed serializing JSON Feed")
+    }
+
+    pub fn render_redirect(
+        &self,
+        redirect: &RedirectTemplateData,
+        template: &str,
+    ) -> eyre::Result<String> {
+        let mut tera = Tera::default();
+        self.configure_tera(&mut tera, &redirect.url)?;
+
+        if let Err(err) = tera.add_raw_template("redirect", template) {
+            bail!(Error::InvalidRedirectPageTemplate {
+                reason: error_reason(&err)
+            });
+        }
+
+        let mut context = self.new_context();
+        context.insert("feed", self);
+        context.insert("redirect", redirect);
+
+        match tera.render("redirect", &context) {
+            Ok(contents) => Ok(contents),
+            Err(err) => bail!(Error::InvalidRedirectPageTemplate {
+                reason: error_reason(&err),
+            }),
+        }
+    }
+
+    pub fn render_rss(&self, template: &str, limit: Option<usize>) -> eyre::Result<String> {
+        let rss_url = self
+            .rss_url
+            .as_deref()
+            .ok_or(eyre!("There is no RSS feed URL. This is a bug."))?;
+
+        let mut tera = Tera::default();
+        self.configure_tera(&mut tera, rss_url)?;
+
+        tera.add_raw_template("rss", template)
+            .wrap_err("The bundled RSS feed template is invalid. This is a bug.")?;
+
+        let context = self.syndication_context(limit)?;
+
+        tera.render("rss", &context)
+            .wrap_err("failed generating the RSS feed")
+    }
+
+    pub fn render_gemsub(&self, template: &str) -> eyre::Result<String> {
+        let gemsub_url = self
+            .gemsub_url
+            .as_deref()
+            .ok_or(eyre!("There is no gemsub feed URL. This is a bug."))?;
+
+        let mut tera = Tera::default();
+        self.configure_tera(&mut tera, gemsub_url)?;
+
+        tera.add_raw_template("gemsub", template)
+            .wrap_err("The bundled gemsub feed template is invalid. This is a bug.")?;
+
+        let mut context = self.new_context();
+        context.insert("feed", self);
+
+        tera.render("gemsub", &context)
+            .wrap_err("failed generating the gemsub feed")
+    }
+}
+
+/// Find every template in the includes directory, along with the name other templates can
+/// `import` or `include` it by, which is its path relative to the includes directory.
+pub fn find_include_files(includes_dir: &Path) -> eyre::Result<Vec<(PathBuf, String)>> {
+    let mut include_files = Vec::new();
+
+    for entry_result in WalkDir::new(includes_dir)
+        .follow_links(true)
+        .sort_by_file_name()
+    {
+        let entry = entry_result.wrap_err(format!(
+            "failed reading includes directory: {}",
+            includes_dir.to_string_lossy()
+        ))?;
+
+        let is_template = entry
+            .path()
+            .extension()
+            .is_some_and(|ext| ext == INCLUDE_FILE_EXT);
+
+        if !entry.file_type().is_file() || !is_template {
+            continue;
+        }
+
+        // Template names always use forward slashes, regardless of the platform.
+        let name = entry
+            .path()
+            .strip_prefix(includes_dir)?
+            .iter()
+            .map(|segment| segment.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        include_files.push((entry.path().to_owned(), name));
+    }
+
+    Ok(include_files)
+}
+
+// Load every template in the includes directory into a Tera instance.
+fn load_includes(tera: &mut Tera, includes_dir: &Path) -> eyre::Result<()> {
+    let include_files = find_include_files(includes_dir)?
+        .into_iter()
+        .map(|(path, name)| (path, Some(name)))
+        .collect::<Vec<_>>();
+
+    if let Err(err) = tera.add_template_files(include_files) {
+        bail!(Error::InvalidIncludeTemplate {
+            reason: error_reason(&err),
+        });
+    }
+
+    Ok(())
+}
+
+/// Check that every template in the includes directory is valid Tera syntax, without rendering
+/// them.
+pub fn check_includes(includes_dir: &Path) -> eyre::Result<()> {
+    load_includes(&mut Tera::default(), includes_dir)
+}
+
+/// Check that a template file is valid Tera syntax, without rendering it.
+///
+/// On failure, this returns the reason the template is invalid.
+pub fn check_template_file(template: &Path, includes_dir: Option<&Path>) -> eyre::Result<()> {
+    let mut tera = Tera::default();
+
+    if let Some(includes_dir) = includes_dir {
+        load_includes(&mut tera, includes_dir)?;
+    }
+
+    if let Err(err) = tera.add_template_file(template, None) {
+        bail!(Error::InvalidTemplate {
+            path: template.to_owned(),
+            reason: error_reason(&err),
+        });
+    }
+
+    Ok(())
+}
+
+// Tera errors often wrap the error that actually describes the problem, such as an error returned
+// by a filter, so we need to include the whole chain of errors to give the user a useful message.
+fn error_reason(err: &tera::Error) -> String {
+    let mut reason = err.to_string();
+    let mut source = std::error::Error::source(err);
+
+    while let Some(err) = source {
+        reason.push_str(&format!("\n{err}"));
+        source = err.source();
     }
+
+    reason
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize)]
-pub struct EntryTemplateData {
-    pub id: String,
-    pub url: String,
-    pub title: String,
-    pub body: String,
-    pub updated: String,
-    pub summary: Option<String>,
-    pub published: Option<String>,
-    pub author: Option<EntryAuthorTemplateData>,
-    pub rights: Option<String>,
-    pub lang: Option<String>,
-    pub categories: Vec<String>,
+// Whether a position in a line of a template is inside a `{{ }}` or `{% %}` tag.
+fn is_inside_tag(line: &str, index: usize) -> bool {
+    let before = &line[..index];
+    let tag_start = before.rfind("{{").max(before.rfind("{%"));
+    let tag_end = before.rfind("}}").max(before.rfind("%}"));
+
+    match (tag_start, tag_end) {
+        (Some(start), Some(end)) => start > end,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
 }
 
-impl From<Entry> for EntryTemplateData {
-    fn from(params: Entry) -> Self {
-        Self {
-            id: params.metadata.id,
-            url: params.url.to_string(),
-            title: params.metadata.title,
-            body: params.body,
-            updated: params.metadata.updated.to_rfc3339(),
-            summary: params.metadata.summary,
-            published: params
-                .metadata
-                .published
-                .as_ref()
-                .map(DateTime::<FixedOffset>::to_rfc3339),
-            author: params.metadata.author.map(Into::into),
-            rights: params.metadata.rights,
-            lang: params.metadata.lang,
-            categories: params.metadata.categories,
+// Tera doesn't say where in a template a rendering error happened, but its error messages usually
+// name the variable, filter, or function in quotes. We look for the first place that name
+// appears in a tag, which is usually the culprit. This returns a 1-based line and column.
+fn locate_render_error(err: &tera::Error, source: &str) -> Option<(usize, usize)> {
+    let mut message = err.to_string();
+    let mut err_source = std::error::Error::source(err);
+
+    // The innermost error is the one which describes the problem.
+    while let Some(err) = err_source {
+        message = err.to_string();
+        err_source = err.source();
+    }
+
+    let quoted = |quote: char| {
+        let (_, rest) = message.split_once(quote)?;
+        rest.split_once(quote).map(|(name, _)| name)
+    };
+
+    // Missing templates are named like `'[name]'`.
+    let name = quoted('`')
+        .or_else(|| quoted('\''))?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+
+    if name.is_empty() {
+        return None;
+    }
+
+    for (line_index, line) in source.lines().enumerate() {
+        for (index, _) in line.match_indices(name) {
+            // Don't match `entry.title` when looking for `entry.tit`.
+            let is_whole_name =
+                !line[index + name.len()..].starts_with(|c: char| c.is_alphanumeric() || c == '_');
+
+            if is_whole_name && is_inside_tag(line, index) {
+                return Some((line_index + 1, line[..index].chars().count() + 1));
+            }
         }
     }
+
+    None
 }
 
-impl EntryTemplateData {
-    pub fn render(
-        &self,
-        feed: &FeedTemplateData,
-        template: &Path,
-        output: &Path,
-    ) -> eyre::Result<()> {
-        let mut tera = Tera::default();
+// Describe an error rendering a template file, including where in the template it happened if we
+// can tell.
+fn render_error_reason(err: &tera::Error, template: &Path) -> String {
+    let location = fs::read_to_string(template)
+        .ok()
+        .and_then(|source| locate_render_error(err, &source));
 
-        if let Err(err) = tera.add_template_file(template, Some("post")) {
-            bail!(Error::InvalidPostPageTemplate {
-                path: output.to_owned(),
-                reason: err.to_string(),
-            });
-        }
+    let template = template.to_string_lossy();
 
-        let mut context = Context::new();
-        context.insert("entry", self);
-        context.insert("feed", feed);
+    match location {
+        Some((line, column)) => format!(
+            "{}\n\nThis is probably at line {line}, column {column} of `{template}`.",
+            error_reason(err)
+        ),
+        None => format!(
+            "{}\n\nThis happened in the template at `{template}`.",
+            error_reason(err)
+        ),
+    }
+}
 
-        let dest_file = File::create(output).wrap_err("failed creating gemlog post page file")?;
+/// Register the custom filters and functions that all our templates share.
+fn configure_tera(tera: &mut Tera, capsule_url: &str) {
+    tera.register_filter("date", date_filter);
+    tera.register_filter("rfc822", rfc822_filter);
+    tera.register_filter("slugify", slugify_filter);
+    tera.register_filter("obfuscate_email", obfuscate_email_filter);
 
-        if let Err(err) = tera.render_to("post", &context, dest_file) {
-            bail!(Error::InvalidPostPageTemplate {
-                path: output.to_owned(),
-                reason: err.to_string(),
-            });
-        }
+    let capsule_url = capsule_url.to_owned();
+    let filter_capsule_url = capsule_url.clone();
 
-        Ok(())
+    tera.register_filter(
+        "absolute_url",
+        move |value: &tera::Value, _: &HashMap<String, tera::Value>| {
+            let url = resolve_url_value("absolute_url", &filter_capsule_url, value)?;
+            Ok(tera::Value::String(url.to_string()))
+        },
+    );
+
+    tera.register_function("url", move |args: &HashMap<String, tera::Value>| {
+        url_function(&capsule_url, args)
+    });
+}
+
+// Resolve a path or URL passed to a filter. Absolute URLs are left alone, and paths are relative to
+// the capsule URL.
+fn resolve_url_value(filter: &str, capsule_url: &str, value: &tera::Value) -> tera::Result<Url> {
+    let path = tera::from_value::<String>(value.clone())
+        .map_err(|_| tera::Error::msg(format!("The `{filter}` filter expects a string.")))?;
+
+    if let Ok(url) = Url::parse(&path) {
+        return Ok(url);
     }
+
+    let capsule_url = Url::parse(capsule_url).map_err(|_| {
+        tera::Error::msg(format!("The capsule URL is not a valid URL: {capsule_url}"))
+    })?;
+
+    join_capsule_url(&capsule_url, &path).map_err(tera::Error::msg)
 }
 
-impl FeedTemplateData {
-    pub fn render_index(&self, template: &Path, output: &Path) -> eyre::Result<()> {
-        let mut tera = Tera::default();
+// The URL of one page relative to another, for linking between them. URLs on another host are left
+// as they are.
+fn relative_url(from: &Url, to: &Url) -> String {
+    if from.scheme() != to.scheme()
+        || from.host_str() != to.host_str()
+        || from.port() != to.port()
+        || to.cannot_be_a_base()
+    {
+        return to.to_string();
+    }
 
-        if let Err(err) = tera.add_template_file(template, Some("index")) {
-            bail!(Error::InvalidIndexPageTemplate {
-                reason: err.to_string()
-            });
+    // The last segment of the page we're linking from is its file name, which is empty if the URL
+    // ends in a slash.
+    let from_dir = match from.path_segments() {
+        Some(segments) => {
+            let mut segments = segments.collect::<Vec<_>>();
+            segments.pop();
+            segments
         }
+        None => return to.to_string(),
+    };
 
-        let mut context = Context::new();
-        context.insert("feed", self);
+    let to_segments = to.path_segments().into_iter().flatten().collect::<Vec<_>>();
+    let (to_file, to_dir) = to_segments.split_last().unwrap_or((&"", &[]));
 
-        let parent_dir = output.parent().ok_or(eyre!(
-            "Could not get parent directory of index page file. This is a bug."
-        ))?;
+    let common = from_dir
+        .iter()
+        .zip(to_dir)
+        .take_while(|(from_segment, to_segment)| from_segment == to_segment)
+        .count();
+
+    let mut relative = "../".repeat(from_dir.len() - common);
 
-        fs::create_dir_all(parent_dir).wrap_err("failed creating parent directory")?;
+    for segment in &to_dir[common..] {
+        relative.push_str(segment);
+        relative.push('/');
+    }
 
-        let dest_file = File::create(output).wrap_err("failed creating gemlog index page file")?;
+    relative.push_str(to_file);
 
-        if let Err(err) = tera.render_to("index", &context, dest_file) {
-            bail!(Error::InvalidIndexPageTemplate {
-                reason: err.to_string(),
-            });
+    // An empty path would link to the page itself, and a colon in the first segment would look
+    // like a URL scheme.
+    if relative.is_empty()
+        || relative
+            .split('/')
+            .next()
+            .is_some_and(|segment| segment.contains(':'))
+    {
+        relative.insert_str(0, "./");
+    }
+
+    if let Some(query) = to.query() {
+        relative.push('?');
+        relative.push_str(query);
+    }
+
+    if let Some(fragment) = to.fragment() {
+        relative.push('#');
+        relative.push_str(fragment);
+    }
+
+    relative
+}
+
+// A Tera filter which turns a path or URL in the capsule into a URL relative to the page being
+// rendered.
+fn relative_url_filter(
+    capsule_url: &str,
+    page_url: &str,
+    value: &tera::Value,
+) -> tera::Result<tera::Value> {
+    let url = resolve_url_value("relative_url", capsule_url, value)?;
+
+    let page_url = Url::parse(page_url).map_err(|_| {
+        tera::Error::msg(format!(
+            "The URL of this page is not a valid URL: {page_url}"
+        ))
+    })?;
+
+    Ok(tera::Value::String(relative_url(&page_url, &url)))
+}
+
+// A Tera function which returns the absolute URL of a path in the capsule.
+fn url_function(
+    capsule_url: &str,
+    args: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let path = match args.get("path") {
+        Some(path) => tera::from_value::<String>(path.clone())
+            .map_err(|_| tera::Error::msg("The `path` argument of `url` must be a string."))?,
+        None => {
+            return Err(tera::Error::msg(
+                "The `url` function requires a `path` argument.",
+            ))
         }
+    };
 
-        Ok(())
+    let capsule_url = Url::parse(capsule_url).map_err(|_| {
+        tera::Error::msg(format!("The capsule URL is not a valid URL: {capsule_url}"))
+    })?;
+
+    let url = join_capsule_url(&capsule_url, &path).map_err(tera::Error::msg)?;
+
+    Ok(tera::Value::String(url.to_string()))
+}
+
+// A Tera function which returns the URL of the post with the given ID.
+fn post_url_function(
+    post_urls: &BTreeMap<String, String>,
+    args: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let id = match args.get("id") {
+        Some(id) => tera::from_value::<String>(id.clone())
+            .map_err(|_| tera::Error::msg("The `id` argument of `post_url` must be a string."))?,
+        None => {
+            return Err(tera::Error::msg(
+                "The `post_url` function requires an `id` argument.",
+            ))
+        }
+    };
+
+    match post_urls.get(&id) {
+        Some(url) => Ok(tera::Value::String(url.clone())),
+        None => Err(tera::Error::msg(format!(
+            "There is no post with the ID `{id}`."
+        ))),
     }
+}
 
-    pub fn render_feed(&self, template: &str, output: &Path) -> eyre::Result<()> {
-        let mut tera = Tera::default();
+// Parse a timestamp passed to a filter. We pass all timestamps to templates in RFC 3339 format.
+fn parse_timestamp_value(filter: &str, value: &tera::Value) -> tera::Result<DateTime<FixedOffset>> {
+    let timestamp = tera::from_value::<String>(value.clone()).map_err(|_| {
+        tera::Error::msg(format!("The `{filter}` filter expects a string timestamp."))
+    })?;
 
-        tera.add_raw_template("feed", template)
-            .wrap_err("The bundled Atom feed template is invalid. This is a bug.")?;
+    DateTime::parse_from_rfc3339(&timestamp).map_err(|_| {
+        tera::Error::msg(format!(
+            "The `{filter}` filter expects an RFC 3339 timestamp, but got `{timestamp}`."
+        ))
+    })
+}
 
-        let mut context = Context::new();
-        context.insert("feed", self);
+// A Tera filter which formats an RFC 3339 timestamp using a strftime-style format string. This
+// replaces the built-in Tera `date` filter so that its behavior is consistent across all our
+// templates and an invalid format string produces a readable error.
+//
+// https://docs.rs/chrono/latest/chrono/format/strftime/index.html
+fn date_filter(
+    value: &tera::Value,
+    args: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let format = match args.get("format") {
+        Some(format) => tera::from_value::<String>(format.clone()).map_err(|_| {
+            tera::Error::msg("The `format` argument of the `date` filter must be a string.")
+        })?,
+        None => String::from("%Y-%m-%d"),
+    };
 
-        let parent_dir = output.parent().ok_or(eyre!(
-            "Could not get parent directory of Atom feed file. This is a bug."
-        ))?;
+    // Formatting a date with an invalid format string panics, so we need to check it first.
+    let items = StrftimeItems::new(&format).collect::<Vec<_>>();
 
-        fs::create_dir_all(parent_dir).wrap_err("failed creating parent directory")?;
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return Err(tera::Error::msg(format!(
+            "The `date` filter was given an invalid format string: `{format}`"
+        )));
+    }
+
+    let datetime = parse_timestamp_value("date", value)?;
 
-        let dest_file = File::create(output).wrap_err("failed creating gemlog Atom feed file")?;
+    Ok(tera::Value::String(
+        datetime.format_with_items(items.into_iter()).to_string(),
+    ))
+}
 
-        tera.render_to("feed", &context, dest_file)
-            .wrap_err("failed generating the Atom feed")?;
+// Describe a time relative to now in words, like "3 days ago" or "in 5 minutes". The units get
+// coarser the further away the time is, and the counts are rounded to the nearest unit.
+fn time_ago(datetime: &DateTime<FixedOffset>, now: &DateTime<FixedOffset>) -> String {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
 
-        Ok(())
+    let seconds = now.signed_duration_since(*datetime).num_seconds();
+    let is_future = seconds < 0;
+    let seconds = seconds.abs();
+
+    let rounded = |unit: i64| (seconds + unit / 2) / unit;
+
+    let description = if seconds < 45 {
+        return String::from("just now");
+    } else if seconds < 90 {
+        String::from("a minute")
+    } else if seconds < 45 * MINUTE {
+        format!("{} minutes", rounded(MINUTE))
+    } else if seconds < 90 * MINUTE {
+        String::from("an hour")
+    } else if seconds < 22 * HOUR {
+        format!("{} hours", rounded(HOUR))
+    } else if seconds < 36 * HOUR {
+        return String::from(if is_future { "tomorrow" } else { "yesterday" });
+    } else if seconds < 26 * DAY {
+        format!("{} days", rounded(DAY))
+    } else if seconds < 45 * DAY {
+        String::from("a month")
+    } else if seconds < 320 * DAY {
+        format!("{} months", rounded(30 * DAY))
+    } else if seconds < 548 * DAY {
+        String::from("a year")
+    } else {
+        format!("{} years", rounded(365 * DAY))
+    };
+
+    if is_future {
+        format!("in {description}")
+    } else {
+        format!("{description} ago")
     }
 }
 
+// A Tera filter which slugifies a string. This replaces the built-in Tera `slugify` filter so that
+// slugs in templates match the slugs gempost generates for category pages.
+fn slugify_filter(
+    value: &tera::Value,
+    _: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let value = tera::from_value::<String>(value.clone())
+        .map_err(|_| tera::Error::msg("The `slugify` filter expects a string."))?;
+
+    Ok(tera::Value::String(slugify(&value)))
+}
+
+const MAILTO_SCHEME: &str = "mailto:";
+
+/// Obfuscate an email address so it's harder to scrape, like `jane [at] example [dot] com`.
+///
+/// This also removes any `mailto:` scheme, since the address wouldn't work as a link anymore.
+pub fn obfuscate_email(email: &str) -> String {
+    email
+        .strip_prefix(MAILTO_SCHEME)
+        .unwrap_or(email)
+        .replace('@', " [at] ")
+        .replace('.', " [dot] ")
+}
+
+// A Tera filter which obfuscates an email address.
+fn obfuscate_email_filter(
+    value: &tera::Value,
+    _: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let value = tera::from_value::<String>(value.clone())
+        .map_err(|_| tera::Error::msg("The `obfuscate_email` filter expects a string."))?;
+
+    Ok(tera::Value::String(obfuscate_email(&value)))
+}
+
+// RSS 2.0 requires dates in RFC 822 format, with the exception that the year may be four digits.
+// We always use a numeric timezone offset rather than a named zone so we preserve the original
+// offset from the metadata file.
+fn to_rfc822(datetime: &DateTime<FixedOffset>) -> String {
+    datetime.format("%a, %d %b %Y %H:%M:%S %z").to_string()
+}
+
+// A Tera filter which converts an RFC 3339 timestamp, which is how we pass all timestamps to
+// templates, into an RFC 822 timestamp.
+fn rfc822_filter(
+    value: &tera::Value,
+    _: &HashMap<String, tera::Value>,
+) -> tera::Result<tera::Value> {
+    let datetime = parse_timestamp_value("rfc822", value)?;
+
+    Ok(tera::Value::String(to_rfc822(&datetime)))
+}
+
 #[derive(Debug)]
 pub struct PostPathParams {
     pub slug: String,
@@ -162,6 +1556,9 @@ pub struct PostPathTemplateData {
     pub year: String,
     pub month: String,
     pub day: String,
+    pub hour: String,
+    pub minute: String,
+    pub doy: String,
     pub slug: String,
 }
 
@@ -181,39 +1578,164 @@ impl From<PostPathParams> for PostPathTemplateData {
                 .published
                 .map(|published| format!("{:0>2}", published.day()))
                 .unwrap_or_default(),
+            hour: params
+                .published
+                .map(|published| format!("{:0>2}", published.hour()))
+                .unwrap_or_default(),
+            minute: params
+                .published
+                .map(|published| format!("{:0>2}", published.minute()))
+                .unwrap_or_default(),
+            doy: params
+                .published
+                .map(|published| format!("{:0>3}", published.ordinal()))
+                .unwrap_or_default(),
             slug: params.slug,
         }
     }
 }
 
+// Render a template for a URL path. On failure, this returns the reason the template is invalid.
+fn render_path_template(
+    template: &str,
+    data: &impl Serialize,
+    capsule_url: &str,
+) -> Result<String, String> {
+    let mut tera = Tera::default();
+    configure_tera(&mut tera, capsule_url);
+
+    tera.add_raw_template("path", template)
+        .map_err(|err| error_reason(&err))?;
+
+    let context = Context::from_serialize(data).map_err(|err| error_reason(&err))?;
+
+    tera.render("path", &context)
+        .map_err(|err| error_reason(&err))
+}
+
 impl PostPathTemplateData {
-    pub fn render(&self, template: &str) -> eyre::Result<String> {
-        let mut tera = Tera::default();
+    pub fn render(&self, template: &str, capsule_url: &str) -> eyre::Result<String> {
+        match render_path_template(template, self, capsule_url) {
+            Ok(path) => Ok(path),
+            Err(reason) => bail!(Error::InvalidPostPath {
+                template: template.to_owned(),
+                reason,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CategoryPathTemplateData {
+    pub slug: String,
+}
 
-        if let Err(err) = tera.add_raw_template("path", template) {
-            bail!(Error::InvalidPostPath {
+impl CategoryPathTemplateData {
+    pub fn render(&self, template: &str, capsule_url: &str) -> eyre::Result<String> {
+        match render_path_template(template, self, capsule_url) {
+            Ok(path) => Ok(path),
+            Err(reason) => bail!(Error::InvalidCategoryPath {
                 template: template.to_owned(),
-                reason: err.to_string(),
-            });
+                reason,
+            }),
         }
+    }
+}
 
-        let mut context = Context::new();
-        context.insert("year", &self.year);
-        context.insert("month", &self.month);
-        context.insert("day", &self.day);
-        context.insert("slug", &self.slug);
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexPagePathTemplateData {
+    pub page: usize,
+}
 
-        match tera.render("path", &context) {
+impl IndexPagePathTemplateData {
+    pub fn render(&self, template: &str, capsule_url: &str) -> eyre::Result<String> {
+        match render_path_template(template, self, capsule_url) {
             Ok(path) => Ok(path),
-            Err(err) => bail!(Error::InvalidPostPath {
+            Err(reason) => bail!(Error::InvalidIndexPagePath {
                 template: template.to_owned(),
-                reason: err.to_string(),
+                reason,
             }),
         }
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CategoryTemplateData {
+    pub name: String,
+    pub slug: String,
+    pub url: String,
+    pub count: usize,
+}
+
+impl CategoryTemplateData {
+    fn from_category(category: Category, entries: &[Entry]) -> Self {
+        let count = entries
+            .iter()
+            .filter(|entry| {
+                entry
+                    .metadata
+                    .categories
+                    .iter()
+                    .any(|name| slugify(name) == category.slug)
+            })
+            .count();
+
+        Self {
+            name: category.name,
+            slug: category.slug,
+            url: category.url.to_string(),
+            count,
+        }
+    }
+}
+
+/// The posts published in a given year, for the archive page.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct ArchiveYearTemplateData<'a> {
+    /// The year, or `None` for posts without a publication date.
+    pub year: Option<i32>,
+    pub entries: Vec<&'a EntryTemplateData>,
+}
+
+impl<'a> ArchiveYearTemplateData<'a> {
+    // Group entries by the year they were published, most recent year first, with undated posts at
+    // the end. Entries within a year stay in the same order as the feed.
+    fn group(entries: &'a [EntryTemplateData]) -> Vec<Self> {
+        let mut entries_by_year = BTreeMap::<cmp::Reverse<Option<i32>>, Vec<_>>::new();
+
+        for entry in entries {
+            entries_by_year
+                .entry(cmp::Reverse(entry.publish_year))
+                .or_default()
+                .push(entry);
+        }
+
+        // `None` sorts before every year, so reversing the order puts it last.
+        entries_by_year
+            .into_iter()
+            .map(|(cmp::Reverse(year), entries)| Self { year, entries })
+            .collect()
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct RedirectTemplateData {
+    pub title: String,
+    pub url: String,
+    pub target: String,
+}
+
+impl From<Redirect> for RedirectTemplateData {
+    fn from(value: Redirect) -> Self {
+        Self {
+            title: value.title,
+            url: value.url.to_string(),
+            target: value.target.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct FeedAuthorTemplateData {
     pub name: String,
     pub email: Option<String>,
@@ -230,31 +1752,349 @@ impl From<FeedAuthor> for FeedAuthorTemplateData {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize)]
+/// Information about the build which is available to every template as `site`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SiteTemplateData {
+    /// The number of posts in the gemlog, not counting unlisted posts.
+    pub post_count: usize,
+    /// When the build started, so it's the same for every page. Post pages which weren't
+    /// re-rendered keep the time of the build which last rendered them.
+    pub build_time: String,
+    pub gempost_version: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct FeedTemplateData {
+    /// The unique ID of the feed, which is the capsule URL for the main feed.
+    pub id: String,
     pub capsule_url: String,
     pub feed_url: String,
+    pub rss_url: Option<String>,
+    pub gemsub_url: Option<String>,
+    pub json_feed_url: Option<String>,
     pub index_url: String,
+    pub categories_url: String,
+    pub archive_url: String,
     pub title: String,
     pub updated: String,
     pub subtitle: Option<String>,
     pub rights: Option<String>,
+    pub icon: Option<String>,
+    pub logo: Option<String>,
+    pub lang: Option<String>,
+    pub category_scheme: String,
     pub author: Option<FeedAuthorTemplateData>,
+    /// The directory of templates to make available to every other template.
+    #[serde(skip)]
+    pub includes_dir: Option<PathBuf>,
+    /// This is in its own variable rather than part of the feed, which keeps the build time out
+    /// of the post fingerprints.
+    #[serde(skip)]
+    pub site: SiteTemplateData,
+    /// How to handle entries without a published date in the syndication feeds.
+    #[serde(skip)]
+    pub missing_published: MissingPublished,
+    /// How many of the most recent entries have their full content in the syndication feeds.
+    #[serde(skip)]
+    pub full_content_limit: Option<usize>,
+    /// The URL of every post by its ID, including posts which aren't in this feed.
+    #[serde(skip)]
+    pub post_urls: BTreeMap<String, String>,
     pub entries: Vec<EntryTemplateData>,
+    pub unlisted_entries: Vec<EntryTemplateData>,
+    /// The entries which were updated well after they were published, most recently updated
+    /// first. This is only available to the index template.
+    #[serde(skip)]
+    pub updated_entries: Vec<EntryTemplateData>,
+    pub categories: Vec<CategoryTemplateData>,
+}
+
+// Posts can have different offsets, so we normalize the feed's timestamps to UTC.
+fn feed_timestamp(datetime: &DateTime<FixedOffset>) -> String {
+    datetime
+        .with_timezone(&Utc)
+        .to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+// The indices of the entries which were updated at least `threshold` after they were published,
+// most recently updated first.
+fn updated_entry_indices(entries: &[Entry], threshold: Duration) -> Vec<usize> {
+    let mut indices = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| {
+            entry.metadata.published.is_some_and(|published| {
+                let since_published = entry.metadata.updated - published;
+                since_published > Duration::zero() && since_published >= threshold
+            })
+        })
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+
+    // This compares the instants, so it works even if the timestamps have different offsets.
+    indices.sort_by_key(|&index| cmp::Reverse(entries[index].metadata.updated));
+
+    indices
 }
 
 impl From<Feed> for FeedTemplateData {
     fn from(feed: Feed) -> Self {
-        Self {
+        let updated_indices = updated_entry_indices(&feed.entries, feed.updated_threshold);
+
+        let entry_options = EntryTemplateOptions {
+            words_per_minute: feed.words_per_minute,
+            summary_length: feed.summary_length,
+            plain_summaries: feed.plain_summaries,
+            more_marker: &feed.more_marker,
+            inline_links: feed.inline_links,
+            default_lang: feed.lang.as_deref(),
+            capsule_url: &feed.capsule_url,
+            breadcrumb_home: &feed.breadcrumb_home,
+            posts_dir: &feed.posts_dir,
+        };
+
+        let mut data = Self {
+            id: feed.capsule_url.to_string(),
             capsule_url: feed.capsule_url.to_string(),
             feed_url: feed.feed_url.to_string(),
+            rss_url: feed.rss_url.as_ref().map(ToString::to_string),
+            gemsub_url: feed.gemsub_url.as_ref().map(ToString::to_string),
+            json_feed_url: feed.json_feed_url.as_ref().map(ToString::to_string),
             index_url: feed.index_url.to_string(),
+            categories_url: feed.categories_url.to_string(),
+            archive_url: feed.archive_url.to_string(),
             title: feed.title,
-            updated: feed.updated.to_rfc3339(),
+            updated: feed_timestamp(&feed.updated),
             subtitle: feed.subtitle,
             rights: feed.rights,
+            icon: feed.icon.as_ref().map(ToString::to_string),
+            logo: feed.logo.as_ref().map(ToString::to_string),
+            lang: feed.lang.clone(),
+            category_scheme: feed.category_scheme,
+            includes_dir: feed.includes_dir,
+            missing_published: feed.missing_published,
+            full_content_limit: feed.full_content_limit,
+            site: SiteTemplateData {
+                post_count: feed.entries.len(),
+                build_time: feed
+                    .build_time
+                    .with_timezone(&Utc)
+                    .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                gempost_version: env!("CARGO_PKG_VERSION"),
+            },
+            post_urls: feed
+                .entries
+                .iter()
+                .chain(&feed.unlisted_entries)
+                .map(|entry| (entry.metadata.id.clone(), entry.url.to_string()))
+                .collect(),
             author: feed.author.map(Into::into),
-            entries: feed.entries.into_iter().map(Into::into).collect(),
+            categories: feed
+                .categories
+                .into_iter()
+                .map(|category| CategoryTemplateData::from_category(category, &feed.entries))
+                .collect(),
+            entries: feed
+                .entries
+                .into_iter()
+                .map(|entry| EntryTemplateData::from_entry(entry, entry_options))
+                .collect(),
+            unlisted_entries: feed
+                .unlisted_entries
+                .into_iter()
+                .map(|entry| EntryTemplateData::from_entry(entry, entry_options))
+                .collect(),
+            updated_entries: Vec::new(),
+        };
+
+        data.updated_entries = updated_indices
+            .into_iter()
+            .map(|index| data.entries[index].clone())
+            .collect();
+
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::Config;
+    use crate::feed::FeedOptions;
+    use crate::xml;
+
+    // A feed with a single post, read the same way `gempost render` reads a post with front matter.
+    fn test_feed(name: &str, config: &str, post: &str) -> FeedTemplateData {
+        let dir = std::env::temp_dir().join(format!("gempost-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let config_path = dir.join("gempost.yaml");
+        fs::write(&config_path, config).unwrap();
+        let config = Config::read(&config_path, None);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let feed = Feed::from_post(
+            &config.unwrap(),
+            FeedOptions::default(),
+            Path::new("posts/test.gmi"),
+            post.to_owned(),
+            None,
+            |_| {},
+        )
+        .unwrap();
+
+        FeedTemplateData::from(feed)
+    }
+
+    #[test]
+    fn feed_timestamps_are_utc() {
+        let datetime = DateTime::parse_from_rfc3339("2024-03-01T09:30:00+05:30").unwrap();
+
+        assert_eq!(feed_timestamp(&datetime), "2024-03-01T04:00:00Z");
+    }
+
+    #[test]
+    fn relative_url_to_sibling() {
+        let from = Url::parse("gemini://example.com/posts/first.gmi").unwrap();
+        let to = Url::parse("gemini://example.com/posts/second.gmi").unwrap();
+
+        assert_eq!(relative_url(&from, &to), "second.gmi");
+    }
+
+    #[test]
+    fn relative_url_to_child() {
+        let from = Url::parse("gemini://example.com/posts/index.gmi").unwrap();
+        let to = Url::parse("gemini://example.com/posts/2024/first.gmi").unwrap();
+
+        assert_eq!(relative_url(&from, &to), "2024/first.gmi");
+    }
+
+    #[test]
+    fn relative_url_to_parent() {
+        let from = Url::parse("gemini://example.com/posts/2024/first.gmi").unwrap();
+        let to = Url::parse("gemini://example.com/index.gmi").unwrap();
+
+        assert_eq!(relative_url(&from, &to), "../../index.gmi");
+    }
+
+    fn yaml(source: &str) -> serde_yaml::Value {
+        serde_yaml::from_str(source).unwrap()
+    }
+
+    #[test]
+    fn template_value_converts_nested_maps() {
+        let value = template_value(yaml("outer:\n  inner: !tagged value\n  1: one\n"));
+
+        assert_eq!(value, yaml("outer:\n  inner: value\n  \"1\": one\n"));
+    }
+
+    #[test]
+    fn template_value_converts_lists() {
+        let value = template_value(yaml("[1, !tagged two, {true: three}]"));
+
+        assert_eq!(value, yaml("[1, two, {\"true\": three}]"));
+    }
+
+    #[test]
+    fn template_value_keeps_bools() {
+        assert_eq!(template_value(yaml("true")), serde_yaml::Value::Bool(true));
+        assert_eq!(
+            template_value(yaml("!tagged false")),
+            serde_yaml::Value::Bool(false)
+        );
+    }
+
+    #[test]
+    fn feed_with_special_characters_is_well_formed() {
+        let feed = test_feed(
+            "escaping",
+            "title: \"Tom & Jerry's <\\\"feed\\\">\"\nurl: \"gemini://example.com\"\n",
+            "---\n\
+             id: \"urn:uuid:7f6a2d3e-0d7a-4b8f-9a52-1c1b7d8e2f4a\"\n\
+             title: \"Fish & chips <\\\"quoted\\\"> 'single'\"\n\
+             summary: \"A < B && C > D, \\\"so\\\" it's 'true'\"\n\
+             updated: \"2024-03-01T12:00:00Z\"\n\
+             ---\n\
+             Body with &<>\"' and ]]> in it.\n",
+        );
+        let atom = feed.render_feed(None, None).unwrap();
+
+        for text in ["&<>\"'", "Tom & Jerry", "Fish & chips", "A < B"] {
+            assert!(!atom.contains(text), "`{text}` wasn't escaped");
         }
+
+        let body = atom
+            .trim_start()
+            .strip_prefix("<?xml version=\"1.0\" encoding=\"utf-8\"?>")
+            .unwrap();
+
+        assert_eq!(xml::check_fragment(body), Ok(()));
+    }
+
+    #[test]
+    fn feed_updated_is_stable_between_builds() {
+        let config = "title: \"Test\"\nurl: \"gemini://example.com\"\n";
+        let post = "---\n\
+                    id: \"urn:uuid:0b5f3c1e-6a0f-4f43-8d6e-3f1f6a9c2b7d\"\n\
+                    title: \"Post\"\n\
+                    updated: \"2024-03-01T12:00:00+01:00\"\n\
+                    ---\n\
+                    Body\n";
+
+        // The feed's `<updated>` is the first one, before any entries.
+        let feed_updated = |atom: &str| {
+            let start = atom.find("<updated>").unwrap() + "<updated>".len();
+            let end = atom.find("</updated>").unwrap();
+            atom[start..end].to_owned()
+        };
+
+        let first = test_feed("stable-first", config, post)
+            .render_feed(None, None)
+            .unwrap();
+        let second = test_feed("stable-second", config, post)
+            .render_feed(None, None)
+            .unwrap();
+
+        assert_eq!(feed_updated(&first), "2024-03-01T11:00:00Z");
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn timeago_is_relative_to_the_build_time() {
+        let mut feed = test_feed(
+            "timeago",
+            "title: \"Test\"\nurl: \"gemini://example.com\"\n",
+            "---\nid: \"post\"\ntitle: \"Post\"\nupdated: \"2024-03-01T12:00:00Z\"\n---\nBody\n",
+        );
+        feed.site.build_time = String::from("2024-03-04T12:00:00Z");
+
+        let template =
+            std::env::temp_dir().join(format!("gempost-timeago-{}.txt", std::process::id()));
+        fs::write(
+            &template,
+            "{% for entry in feed.entries %}{{ entry.updated | timeago }}{% endfor %}",
+        )
+        .unwrap();
+        let rendered = feed.render_robots(&template);
+        fs::remove_file(&template).unwrap();
+
+        assert_eq!(rendered.unwrap(), "3 days ago");
+    }
+
+    #[test]
+    fn obfuscates_plus_addressed_subdomain_emails() {
+        assert_eq!(
+            obfuscate_email("a+b@mail.example.org"),
+            "a+b [at] mail [dot] example [dot] org"
+        );
+    }
+
+    #[test]
+    fn obfuscating_removes_mailto() {
+        assert_eq!(
+            obfuscate_email("mailto:jane@example.com"),
+            "jane [at] example [dot] com"
+        );
     }
 }
@@ -0,0 +1,218 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use eyre::bail;
+use log::{error, info, warn};
+
+use crate::build::{post_pages, PostPage};
+use crate::config::Config;
+use crate::entry::check_posts;
+use crate::error::Error;
+use crate::feed::{metadata_options, post_locator, Feed, FeedOptions};
+use crate::template::{
+    check_includes, check_template_file, EntryLinkTemplateData, FeedTemplateData,
+    PaginationTemplateData,
+};
+
+// Describe an error the same way `main` would, but without exiting.
+fn describe_error(err: &eyre::Report) -> String {
+    match err.downcast_ref::<Error>() {
+        Some(user_err) => user_err.to_string(),
+        None => format!("{:?}", err),
+    }
+}
+
+// Check that every template in the config parses.
+fn check_templates(
+    templates: &BTreeSet<&Path>,
+    includes_dir: Option<&Path>,
+    problems: &mut Vec<String>,
+) {
+    for template in templates {
+        if let Err(err) = check_template_file(template, includes_dir) {
+            problems.push(describe_error(&err));
+        }
+    }
+}
+
+// Render every page which depends on the posts, without writing anything.
+fn check_rendering(config: &Config, feed: &Feed, problems: &mut Vec<String>) {
+    let feed_data = FeedTemplateData::from(feed.clone());
+
+    let entry_links = feed_data
+        .entries
+        .iter()
+        .map(EntryLinkTemplateData::from)
+        .collect::<Vec<_>>();
+
+    let post_pages = post_pages(feed, &feed_data, &entry_links);
+
+    for (index, page) in feed.index_pages.iter().enumerate() {
+        let pagination = PaginationTemplateData::new(&feed.index_pages, index);
+
+        if let Err(err) = feed_data.render_index(
+            &feed_data.entries[page.entries.clone()],
+            &pagination,
+            &config.index_template_file,
+        ) {
+            problems.push(describe_error(&err));
+        }
+    }
+
+    if let Err(err) = feed_data.render_feed(config.feed_template_file.as_deref(), config.feed_limit)
+    {
+        problems.push(describe_error(&err));
+    }
+
+    for subfeed in &feed.subfeeds {
+        let subfeed_data = feed_data.subfeed(subfeed);
+        let pagination = PaginationTemplateData::new(std::slice::from_ref(&subfeed.index_page), 0);
+
+        if let Err(err) = subfeed_data.render_index(
+            &subfeed_data.entries,
+            &pagination,
+            &config.index_template_file,
+        ) {
+            problems.push(describe_error(&err));
+        }
+
+        if let Err(err) =
+            subfeed_data.render_feed(config.feed_template_file.as_deref(), config.feed_limit)
+        {
+            problems.push(describe_error(&err));
+        }
+    }
+
+    for PostPage {
+        entry,
+        data: entry_data,
+        prev,
+        next,
+    } in post_pages
+    {
+        let template = entry
+            .metadata
+            .template
+            .as_deref()
+            .unwrap_or(&config.post_template_file);
+
+        if let Err(err) = entry_data.render(&feed_data, prev, next, template, &entry.path) {
+            problems.push(format!(
+                "{}\n\nPost: {}",
+                describe_error(&err),
+                entry.source_path.to_string_lossy()
+            ));
+        }
+    }
+}
+
+/// Check the posts and templates for problems without writing any files.
+///
+/// This checks drafts and expired posts too, and it reports every problem it finds rather than
+/// stopping at the first one.
+pub fn validate_capsule(config: &Config) -> eyre::Result<()> {
+    let warn_handler = |msg: &str| warn!("{}", msg);
+    let mut problems = Vec::new();
+
+    let templates = [
+        Some(&config.index_template_file),
+        Some(&config.post_template_file),
+        config.category_template_file.as_ref(),
+        config.categories_template_file.as_ref(),
+        config.redirect_template_file.as_ref(),
+        config.archive_template_file.as_ref(),
+        config.feed_template_file.as_ref(),
+        config.robots_template_file.as_ref(),
+        config.security_template_file.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(AsRef::as_ref)
+    .collect::<BTreeSet<&Path>>();
+
+    // Every template loads the includes, so if any of them are broken, every template would fail
+    // for the same reason.
+    match &config.includes_dir {
+        Some(includes_dir) => match check_includes(includes_dir) {
+            Ok(()) => check_templates(&templates, Some(includes_dir), &mut problems),
+            Err(err) => problems.push(describe_error(&err)),
+        },
+        None => check_templates(&templates, None, &mut problems),
+    }
+
+    // The feed warns about unexpected files when it's assembled, so they aren't reported twice.
+    let has_valid_posts = match check_posts(
+        &config.posts_dir,
+        &config.post_files,
+        post_locator(config),
+        metadata_options(config),
+        true,
+        |_| {},
+    ) {
+        Ok(post_errors) => {
+            let has_valid_posts = post_errors.is_empty();
+
+            for err in post_errors {
+                problems.push(describe_error(&err));
+            }
+
+            has_valid_posts
+        }
+        Err(err) => {
+            problems.push(describe_error(&err));
+            false
+        }
+    };
+
+    // If any posts are invalid, we can't assemble the feed, and we've already reported why.
+    if has_valid_posts {
+        let feed_options = FeedOptions {
+            include_drafts: true,
+            ignore_expiry: true,
+            since: None,
+            until: None,
+            include_unpublished: false,
+            lenient_dates: false,
+            strict_post_files: true,
+            warn_missing_alt_text: true,
+        };
+
+        match Feed::from_config(config, feed_options, warn_handler) {
+            Ok(feed) => {
+                let post_templates = feed
+                    .entries
+                    .iter()
+                    .filter_map(|entry| entry.metadata.template.as_deref())
+                    .filter(|template| !templates.contains(template))
+                    .collect::<BTreeSet<_>>();
+
+                check_templates(
+                    &post_templates,
+                    config.includes_dir.as_deref(),
+                    &mut problems,
+                );
+
+                // A broken template would fail to render every page that uses it, and we've
+                // already reported it once.
+                if problems.is_empty() {
+                    check_rendering(config, &feed, &mut problems);
+                }
+            }
+            Err(err) => problems.push(describe_error(&err)),
+        }
+    }
+
+    for problem in &problems {
+        error!("{problem}\n");
+    }
+
+    if !problems.is_empty() {
+        bail!(Error::ValidationFailed {
+            count: problems.len(),
+        });
+    }
+
+    info!("No problems found.");
+
+    Ok(())
+}
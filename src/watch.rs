@@ -0,0 +1,192 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use eyre::WrapErr;
+use log::{error, info, warn};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::build::{build_capsule, BuildOptions};
+use crate::config::Config;
+use crate::error::Error;
+
+// How long to wait for more changes before rebuilding. Editors often write several events for a
+// single save, and we want to coalesce them into one rebuild.
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(250);
+
+// Print an error without exiting, the same way `main` would.
+fn report_error(err: &eyre::Report) {
+    match err.downcast_ref::<Error>() {
+        Some(user_err) => error!("{}", user_err),
+        None => error!("{:?}", err),
+    }
+}
+
+fn absolute_path(path: &Path) -> PathBuf {
+    match env::current_dir() {
+        Ok(current_dir) => current_dir.join(path),
+        Err(_) => path.to_owned(),
+    }
+}
+
+// The paths we watch for changes, along with whether we need to watch them recursively.
+fn watched_paths(config_path: &Path, config: Option<&Config>) -> Vec<(PathBuf, RecursiveMode)> {
+    let parent_dir = |path: &Path| match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => absolute_path(parent),
+        _ => absolute_path(Path::new(".")),
+    };
+
+    // We always watch the config file, so we can pick up fixes to an invalid config.
+    let mut paths = vec![(parent_dir(config_path), RecursiveMode::NonRecursive)];
+
+    if let Some(config) = config {
+        paths.push((absolute_path(&config.posts_dir), RecursiveMode::Recursive));
+        paths.push((absolute_path(&config.static_dir), RecursiveMode::Recursive));
+
+        if let Some(includes_dir) = &config.includes_dir {
+            paths.push((absolute_path(includes_dir), RecursiveMode::Recursive));
+        }
+
+        if let Some(snippets_dir) = &config.snippets_dir {
+            paths.push((absolute_path(snippets_dir), RecursiveMode::Recursive));
+        }
+
+        for asset in &config.assets {
+            paths.push((absolute_path(&asset.dir), RecursiveMode::Recursive));
+        }
+
+        // We watch the directories containing templates rather than the templates themselves,
+        // because many editors save files by replacing them.
+        let template_files = [
+            Some(&config.index_template_file),
+            Some(&config.post_template_file),
+            config.category_template_file.as_ref(),
+            config.categories_template_file.as_ref(),
+            config.redirect_template_file.as_ref(),
+            config.archive_template_file.as_ref(),
+            config.feed_template_file.as_ref(),
+            config.robots_template_file.as_ref(),
+            config.security_template_file.as_ref(),
+        ];
+
+        for template_file in template_files.into_iter().flatten() {
+            paths.push((parent_dir(template_file), RecursiveMode::NonRecursive));
+        }
+    }
+
+    paths.sort();
+    paths.dedup_by(|(a, _), (b, _)| a == b);
+
+    paths
+}
+
+// Whether a filesystem event should trigger a rebuild. We need to ignore changes to files that the
+// build itself writes, or we would rebuild forever.
+fn is_relevant(event: &Event, ignored_paths: &[PathBuf]) -> bool {
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+
+    event.paths.iter().any(|path| {
+        !ignored_paths
+            .iter()
+            .any(|ignored_path| path.starts_with(ignored_path))
+    })
+}
+
+// Block until a relevant change happens, and then wait for changes to settle.
+fn wait_for_changes(
+    receiver: &Receiver<notify::Result<Event>>,
+    ignored_paths: &[PathBuf],
+) -> eyre::Result<()> {
+    loop {
+        let event = receiver
+            .recv()
+            .wrap_err("the file watcher stopped unexpectedly")?;
+
+        match event {
+            Ok(event) if is_relevant(&event, ignored_paths) => break,
+            Ok(_) => continue,
+            Err(err) => warn!("Error watching files: {}", err),
+        }
+    }
+
+    loop {
+        match receiver.recv_timeout(DEBOUNCE_DURATION) {
+            Ok(_) => continue,
+            Err(RecvTimeoutError::Timeout) => return Ok(()),
+            Err(RecvTimeoutError::Disconnected) => {
+                eyre::bail!("the file watcher stopped unexpectedly")
+            }
+        }
+    }
+}
+
+/// Build the capsule, and then rebuild it whenever the posts, templates, static content, or config
+/// file change.
+///
+/// Errors during a rebuild are printed rather than returned, so that the user can fix them without
+/// restarting the watcher. This only returns if the watcher itself fails.
+pub fn watch_capsule(config_path: &Path, options: &BuildOptions) -> eyre::Result<()> {
+    let (sender, receiver) = mpsc::channel();
+
+    let mut watcher = RecommendedWatcher::new(sender, notify::Config::default())
+        .wrap_err("failed creating file watcher")?;
+
+    let mut current_paths = Vec::new();
+    let mut ignored_paths = Vec::new();
+
+    loop {
+        let config = match Config::read(config_path, options.profile.as_deref()) {
+            Ok(mut config) => {
+                options.apply_to_config(&mut config);
+                Some(config)
+            }
+            Err(err) => {
+                report_error(&err.wrap_err("failed reading the gempost config file"));
+                None
+            }
+        };
+
+        // The config might have changed which paths we need to watch.
+        let new_paths = watched_paths(config_path, config.as_ref());
+
+        if new_paths != current_paths {
+            for (path, _) in &current_paths {
+                // The path might have been deleted, in which case it's no longer being watched.
+                let _ = watcher.unwatch(path);
+            }
+
+            for (path, mode) in &new_paths {
+                if let Err(err) = watcher.watch(path, *mode) {
+                    warn!(
+                        "Could not watch this path for changes: {} ({})",
+                        path.to_string_lossy(),
+                        err
+                    );
+                }
+            }
+
+            current_paths = new_paths;
+        }
+
+        if let Some(config) = &config {
+            ignored_paths = vec![
+                absolute_path(options.public_dir(config)),
+                absolute_path(&config.cache_file),
+            ];
+
+            if let Some(report_path) = &options.report {
+                ignored_paths.push(absolute_path(report_path));
+            }
+
+            match build_capsule(config, options).wrap_err("failed building the capsule") {
+                Ok(()) => info!("Watching for changes..."),
+                Err(err) => report_error(&err),
+            }
+        }
+
+        wait_for_changes(&receiver, &ignored_paths)?;
+    }
+}
@@ -0,0 +1,268 @@
+// These are the only parts of XML we accept in a fragment besides elements and text.
+const COMMENT_START: &str = "<!--";
+const COMMENT_END: &str = "-->";
+const CDATA_START: &str = "<![CDATA[";
+const CDATA_END: &str = "]]>";
+
+// Without a DTD, these are the only named entities XML defines.
+const PREDEFINED_ENTITIES: &[&str] = &["amp", "lt", "gt", "quot", "apos"];
+
+fn is_name_start_char(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == ':'
+}
+
+fn is_name_char(c: char) -> bool {
+    is_name_start_char(c) || c.is_numeric() || c == '-' || c == '.'
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+    match name.chars().next() {
+        Some(first) if is_name_start_char(first) && name.chars().all(is_name_char) => Ok(()),
+        _ => Err(format!(
+            "`{name}` is not a valid element or attribute name."
+        )),
+    }
+}
+
+// Check that every `&` in some text starts an entity or character reference.
+fn check_text(text: &str) -> Result<(), String> {
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        let after_start = &rest[start + 1..];
+
+        let reference = match after_start.find(';') {
+            Some(end) => &after_start[..end],
+            None => {
+                return Err(String::from(
+                    "There is an `&` which isn't escaped as `&amp;`.",
+                ))
+            }
+        };
+
+        let is_valid = match reference.strip_prefix('#') {
+            Some(code) => match code.strip_prefix('x') {
+                Some(hex) => !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()),
+                None => !code.is_empty() && code.chars().all(|c| c.is_ascii_digit()),
+            },
+            None if PREDEFINED_ENTITIES.contains(&reference) => true,
+            None if check_name(reference).is_ok() => {
+                return Err(format!(
+                    "`&{reference};` is not defined in XML. Only `&amp;`, `&lt;`, `&gt;`, `&quot;`, and `&apos;` are, so use a character reference like `&#160;` instead."
+                ))
+            }
+            None => false,
+        };
+
+        if !is_valid {
+            return Err(format!("`&{reference};` is not a valid reference."));
+        }
+
+        rest = &after_start[reference.len() + 1..];
+    }
+
+    if text.contains(CDATA_END) {
+        return Err(String::from("There is a `]]>` outside of a CDATA section."));
+    }
+
+    Ok(())
+}
+
+// Find the `>` which ends a tag, skipping any in quoted attribute values.
+fn find_tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+
+    for (index, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '>') => return Some(index),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+// Check the attributes in a start tag, which is everything after the element name.
+fn check_attributes(attributes: &str) -> Result<(), String> {
+    let mut rest = attributes.trim_start();
+
+    while !rest.is_empty() {
+        let (name, after_name) = rest
+            .split_once('=')
+            .ok_or_else(|| format!("The attribute `{}` has no value.", rest.trim()))?;
+
+        check_name(name.trim())?;
+
+        let after_name = after_name.trim_start();
+
+        let quote = match after_name.chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => {
+                return Err(format!(
+                    "The value of the attribute `{}` must be in quotes.",
+                    name.trim()
+                ))
+            }
+        };
+
+        let value_end = after_name[1..].find(quote).ok_or_else(|| {
+            format!(
+                "The value of the attribute `{}` has no closing quote.",
+                name.trim()
+            )
+        })?;
+
+        let value = &after_name[1..value_end + 1];
+
+        if value.contains('<') {
+            return Err(format!(
+                "The value of the attribute `{}` contains a `<`.",
+                name.trim()
+            ));
+        }
+
+        check_text(value)?;
+
+        rest = after_name[value_end + 2..].trim_start();
+    }
+
+    Ok(())
+}
+
+/// Check that a fragment of XML, like the contents of an element, is well-formed.
+///
+/// A fragment can contain any number of elements, along with text, comments, and CDATA sections.
+/// This only checks the syntax; it doesn't check that namespace prefixes are declared.
+pub fn check_fragment(xml: &str) -> Result<(), String> {
+    let mut open_elements = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        check_text(&rest[..start])?;
+        rest = &rest[start..];
+
+        if let Some(after_start) = rest.strip_prefix(COMMENT_START) {
+            let end = after_start
+                .find(COMMENT_END)
+                .ok_or_else(|| String::from("There is a comment with no closing `-->`."))?;
+            rest = &after_start[end + COMMENT_END.len()..];
+            continue;
+        }
+
+        if let Some(after_start) = rest.strip_prefix(CDATA_START) {
+            let end = after_start
+                .find(CDATA_END)
+                .ok_or_else(|| String::from("There is a CDATA section with no closing `]]>`."))?;
+            rest = &after_start[end + CDATA_END.len()..];
+            continue;
+        }
+
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            return Err(String::from(
+                "Only elements, text, comments, and CDATA sections are allowed.",
+            ));
+        }
+
+        let end = find_tag_end(rest)
+            .ok_or_else(|| String::from("There is a tag with no closing `>`."))?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim_end();
+
+            match open_elements.pop() {
+                Some(open_name) if open_name == name => {}
+                Some(open_name) => {
+                    return Err(format!(
+                        "The element `<{open_name}>` is closed by `</{name}>`."
+                    ))
+                }
+                None => return Err(format!("The element `</{name}>` was never opened.")),
+            }
+
+            continue;
+        }
+
+        let (tag, is_empty_element) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+
+        let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+        let (name, attributes) = tag.split_at(name_end);
+
+        check_name(name)?;
+        check_attributes(attributes)?;
+
+        if !is_empty_element {
+            open_elements.push(name);
+        }
+    }
+
+    check_text(rest)?;
+
+    match open_elements.last() {
+        Some(name) => Err(format!("The element `<{name}>` is never closed.")),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_well_formed_fragments() {
+        assert_eq!(
+            check_fragment(
+                "<credit role='photo' href=\"a?b=1&amp;c=2\">A &lt; B &#160; &#xA0;</credit><br/>"
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rejects_mismatched_tags() {
+        assert!(check_fragment("<a><b></a></b>").is_err());
+        assert!(check_fragment("<a>").is_err());
+        assert!(check_fragment("</a>").is_err());
+    }
+
+    #[test]
+    fn rejects_unquoted_attributes() {
+        assert!(check_fragment("<a href=foo>text</a>").is_err());
+        assert!(check_fragment("<a href>text</a>").is_err());
+        assert!(check_fragment("<a href=\"foo>text</a>").is_err());
+    }
+
+    #[test]
+    fn rejects_stray_ampersands() {
+        assert!(check_fragment("Fish & chips").is_err());
+        assert!(check_fragment("<a title=\"Fish & chips\"/>").is_err());
+        assert!(check_fragment("&#xZZ;").is_err());
+    }
+
+    #[test]
+    fn allows_comments_and_cdata() {
+        assert_eq!(
+            check_fragment("<!-- <not> & a tag --><![CDATA[<raw> & text]]>"),
+            Ok(())
+        );
+        assert!(check_fragment("<!-- never closed").is_err());
+        assert!(check_fragment("<![CDATA[never closed").is_err());
+        assert!(check_fragment("stray ]]> end").is_err());
+    }
+
+    #[test]
+    fn rejects_undefined_entities() {
+        assert!(check_fragment("<credit>A&nbsp;B</credit>").is_err());
+        assert!(check_fragment("&copy;").is_err());
+
+        for entity in PREDEFINED_ENTITIES {
+            assert_eq!(check_fragment(&format!("&{entity};")), Ok(()));
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use walkdir::WalkDir;
+
+const GEMPOST: &str = env!("CARGO_BIN_EXE_gempost");
+
+fn gempost(dir: &Path, args: &[&str]) {
+    let output = Command::new(GEMPOST)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("failed running gempost");
+
+    assert!(
+        output.status.success(),
+        "`gempost {}` failed:\n{}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+// Every file in a directory, by its path relative to the directory, along with its contents.
+fn read_tree(dir: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+    WalkDir::new(dir)
+        .sort_by_file_name()
+        .into_iter()
+        .map(Result::unwrap)
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| {
+            (
+                entry.path().strip_prefix(dir).unwrap().to_owned(),
+                fs::read(entry.path()).unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn building_twice_gives_identical_output() {
+    let capsule_dir =
+        std::env::temp_dir().join(format!("gempost-reproducible-{}", std::process::id()));
+
+    let _ = fs::remove_dir_all(&capsule_dir);
+    fs::create_dir_all(&capsule_dir).unwrap();
+
+    gempost(&capsule_dir, &["init", "."]);
+
+    // The example config leaves the required fields for the user to fill in.
+    let config_path = capsule_dir.join("gempost.yaml");
+    let config = fs::read_to_string(&config_path).unwrap();
+    fs::write(
+        &config_path,
+        config
+            .replace("#title: ", "title: ")
+            .replace("#url: ", "url: "),
+    )
+    .unwrap();
+
+    gempost(
+        &capsule_dir,
+        &["new", "second-post", "--title", "Second post"],
+    );
+    gempost(
+        &capsule_dir,
+        &["new", "third-post", "--title", "Third post"],
+    );
+
+    gempost(&capsule_dir, &["build", "--force", "--output", "first"]);
+    gempost(&capsule_dir, &["build", "--force", "--output", "second"]);
+
+    let first = read_tree(&capsule_dir.join("first"));
+    let second = read_tree(&capsule_dir.join("second"));
+
+    fs::remove_dir_all(&capsule_dir).unwrap();
+
+    assert!(!first.is_empty());
+    assert_eq!(first, second);
+}